@@ -0,0 +1,189 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display, Formatter};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul};
+
+/// 任意精度的无符号整数，用于统计 DFA 所接受的字符串数目
+///
+/// 以 2^32 为基数、低位在前存放，最高位非零（零表示为空）
+#[derive(Clone, Default, Eq, PartialEq, Hash)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    #[inline]
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// 二进制位数，零的位数为 0
+    #[inline]
+    pub fn bits(&self) -> u64 {
+        match self.limbs.last() {
+            Some(&high) => self.limbs.len() as u64 * 32 - high.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// 数值不超过 `u64::MAX` 时转为 `u64`
+    pub fn to_u64(&self) -> Option<u64> {
+        match *self.limbs {
+            [] => Some(0),
+            [low] => Some(low.into()),
+            [low, high] => Some(u64::from(high) << 32 | u64::from(low)),
+            _ => None,
+        }
+    }
+
+    /// 由低位在前的 32 位数字构建
+    #[inline]
+    pub(crate) fn from_limbs(limbs: Vec<u32>) -> Self {
+        let mut big = Self { limbs };
+        big.normalize();
+        big
+    }
+
+    /// 除以 `divisor`，返回余数
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let current = rem << 32 | u64::from(*limb);
+            *limb = (current / u64::from(divisor)) as u32;
+            rem = current % u64::from(divisor);
+        }
+        self.normalize();
+        rem as u32
+    }
+
+    /// 去除高位的零
+    #[inline]
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+}
+
+impl From<u64> for BigUint {
+    #[inline]
+    fn from(n: u64) -> Self {
+        Self::from_limbs(vec![n as u32, (n >> 32) as u32])
+    }
+}
+
+impl From<usize> for BigUint {
+    #[inline]
+    fn from(n: usize) -> Self {
+        Self::from(n as u64)
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, rhs: &BigUint) {
+        if self.limbs.len() < rhs.limbs.len() {
+            self.limbs.resize(rhs.limbs.len(), 0);
+        }
+
+        let mut carry = 0u64;
+        for (i, limb) in self.limbs.iter_mut().enumerate() {
+            let sum = u64::from(*limb) + u64::from(rhs.limbs.get(i).copied().unwrap_or(0)) + carry;
+            *limb = sum as u32;
+            carry = sum >> 32;
+            if carry == 0 && i >= rhs.limbs.len() {
+                break;
+            }
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+}
+
+impl Add<&BigUint> for BigUint {
+    type Output = BigUint;
+
+    #[inline]
+    fn add(mut self, rhs: &BigUint) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl Mul<u64> for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, rhs: u64) -> Self::Output {
+        // 拆为两个 32 位的乘数，避免中间结果溢出
+        let mul_small = |factor: u32, shift: usize| {
+            let mut limbs = vec![0; shift];
+            let mut carry = 0u64;
+            for &limb in &self.limbs {
+                let product = u64::from(limb) * u64::from(factor) + carry;
+                limbs.push(product as u32);
+                carry = product >> 32;
+            }
+            limbs.push(carry as u32);
+            BigUint::from_limbs(limbs)
+        };
+        mul_small(rhs as u32, 0) + &mul_small((rhs >> 32) as u32, 1)
+    }
+}
+
+impl<'a> Sum<&'a BigUint> for BigUint {
+    #[inline]
+    fn sum<I: Iterator<Item = &'a BigUint>>(iter: I) -> Self {
+        iter.fold(BigUint::zero(), |sum, n| sum + n)
+    }
+}
+
+impl Ord for BigUint {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigUint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // 每次除以 10^9，得到的余数即为低 9 位十进制数字
+        const BASE: u32 = 1_000_000_000;
+        let mut n = self.clone();
+        let mut chunks = Vec::new();
+        loop {
+            chunks.push(n.div_rem_small(BASE));
+            if n.is_zero() {
+                break;
+            }
+        }
+
+        let mut chunks = chunks.into_iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for BigUint {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
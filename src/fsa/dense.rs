@@ -0,0 +1,306 @@
+use crate::fsa::dfa::{State, DFA};
+use crate::fsa::{split_symbols, CompileError, StateID, Symbol};
+use std::collections::HashMap;
+use std::mem::{size_of, size_of_val};
+
+/// 稠密 DFA：状态为 `u32` 下标，转移存于扁平表中
+///
+/// 字母表按等价类压缩：在所有状态上转移均一致的字符归为同一类，
+/// 转移表的第 `state * stride + class` 项即为下一状态。下标 0 为死状态
+#[derive(Clone, Debug)]
+pub struct DenseDFA {
+    /// 码点 0..256 的等价类，字节模式下覆盖全部输入
+    byte_classes: [u32; 256],
+    /// 以区间起点升序排列的 (起点, 等价类)，覆盖全部码点
+    classes: Vec<(u32, u32)>,
+    /// 等价类数目
+    stride: usize,
+    table: Vec<u32>,
+    /// 各状态接受的规则编号，升序排列
+    rules: Vec<Box<[usize]>>,
+    start: u32,
+}
+
+impl DenseDFA {
+    /// 死状态，一经进入便不再离开且不可接受
+    ///
+    /// 惰性 DFA（见 `LazyDFA`）的缓存同样以 0 为死状态
+    pub const DEAD: u32 = 0;
+
+    #[inline]
+    pub fn from_dfa(dfa: &DFA) -> Self {
+        Self::from_dfa_with_limit(dfa, usize::MAX).unwrap()
+    }
+
+    /// 由 DFA 构建，占用内存（字节）超过 `memory_limit` 时返回错误
+    ///
+    /// 先求出等价类，在分配转移表之前即按其大小拒绝
+    pub fn from_dfa_with_limit(dfa: &DFA, memory_limit: usize) -> Result<Self, CompileError> {
+        // DFA 状态 `id` 对应下标 `id + 1`，0 留给死状态
+        let index_of = |id: StateID| id.index() as u32 + 1;
+
+        // 将码点空间按所有转移拆分为基本区间，区间之间的空隙同样自成区间
+        let segments = split_symbols(
+            dfa.states()
+                .flat_map(|(_, state)| state.transitions().map(|(symbol, _)| symbol)),
+        );
+        let mut intervals = Vec::with_capacity(segments.len() * 2 + 1);
+        let mut point = 0;
+        for (start, end) in segments.iter().filter_map(|s| s.range()) {
+            if point < start as u32 {
+                intervals.push((point, None));
+            }
+            intervals.push((start as u32, Some(Symbol::Range(start, end))));
+            point = end as u32 + 1;
+        }
+        if point <= char::MAX as u32 {
+            intervals.push((point, None));
+        }
+
+        // 逐个状态细分等价类：原属同一类且在该状态上去向相同的区间仍归入同一类，
+        // 类号按首次出现的顺序分配，所需内存只与区间数目成正比
+        let next_of = |state: &State, symbol: Option<Symbol>| {
+            symbol
+                .and_then(|symbol| state.next_state(symbol))
+                .map_or(Self::DEAD, index_of)
+        };
+        let mut interval_classes = vec![0; intervals.len()];
+        let mut stride = 1;
+        for (_, state) in dfa.states() {
+            let mut split = HashMap::<(u32, u32), u32>::new();
+            for (&(_, symbol), class) in intervals.iter().zip(&mut interval_classes) {
+                let next = split.len() as u32;
+                *class = *split
+                    .entry((*class, next_of(state, symbol)))
+                    .or_insert(next);
+            }
+            stride = split.len();
+        }
+
+        let table_usage = (dfa.len() + 1) * stride * size_of::<u32>();
+        if table_usage > memory_limit {
+            return Err(CompileError::TooMuchMemory {
+                used: table_usage,
+                limit: memory_limit,
+            });
+        }
+
+        let mut table = vec![Self::DEAD; (dfa.len() + 1) * stride];
+        for (i, (_, state)) in dfa.states().enumerate() {
+            for (&(_, symbol), &class) in intervals.iter().zip(&interval_classes) {
+                table[(i + 1) * stride + class as usize] = next_of(state, symbol);
+            }
+        }
+
+        let mut classes = intervals
+            .iter()
+            .zip(interval_classes)
+            .map(|(&(point, _), class)| (point, class))
+            .collect::<Vec<_>>();
+        classes.dedup_by_key(|&mut (_, class)| class);
+
+        let mut dense = Self {
+            byte_classes: [0; 256],
+            classes,
+            stride,
+            table,
+            rules: [Box::default()]
+                .into_iter()
+                .chain(
+                    dfa.states()
+                        .map(|(_, state)| state.rules().iter().copied().collect()),
+                )
+                .collect(),
+            start: index_of(dfa.start()),
+        };
+        for byte in 0..=u8::MAX {
+            dense.byte_classes[byte as usize] = dense.search_class(byte.into());
+        }
+        match dense.memory_usage() {
+            used if used > memory_limit => Err(CompileError::TooMuchMemory {
+                used,
+                limit: memory_limit,
+            }),
+            _ => Ok(dense),
+        }
+    }
+
+    #[inline]
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// 状态数目（含死状态）
+    #[inline]
+    pub fn state_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// 转移表等占用的内存（字节）
+    #[inline]
+    pub fn memory_usage(&self) -> usize {
+        size_of_val(&self.byte_classes)
+            + self.classes.len() * size_of::<(u32, u32)>()
+            + self.table.len() * size_of::<u32>()
+            + self.rules.iter().map(|r| size_of_val(&**r)).sum::<usize>()
+    }
+
+    /// 等价类数目
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    #[inline]
+    pub fn is_dead(&self, state: u32) -> bool {
+        state == Self::DEAD
+    }
+
+    #[inline]
+    pub fn acceptable(&self, state: u32) -> bool {
+        !self.rules[state as usize].is_empty()
+    }
+
+    /// 状态接受的规则编号，多条规则同时接受时取编号最小者
+    #[inline]
+    pub fn rule(&self, state: u32) -> Option<usize> {
+        self.rules[state as usize].first().copied()
+    }
+
+    /// 状态接受的所有规则编号，升序排列
+    #[inline]
+    pub fn rules(&self, state: u32) -> &[usize] {
+        &self.rules[state as usize]
+    }
+
+    /// 根据符号获取下一个状态，ε 或区间符号以其起点为准
+    #[inline]
+    pub fn next_state(&self, state: u32, symbol: Symbol) -> u32 {
+        match symbol {
+            Symbol::Byte(byte) => self.next_byte(state, byte),
+            symbol => match symbol.range() {
+                Some((start, _)) => self.next_class(state, self.class_of(start as u32)),
+                None => state,
+            },
+        }
+    }
+
+    /// 根据字节获取下一个状态，字节模式下的快速路径
+    #[inline]
+    pub fn next_byte(&self, state: u32, byte: u8) -> u32 {
+        self.next_class(state, self.byte_classes[byte as usize])
+    }
+
+    /// 根据符号序列获取最终可达状态，落入死状态时返回 `None`
+    pub fn end_of(&self, symbols: impl IntoIterator<Item = Symbol>) -> Option<u32> {
+        let mut state = self.start;
+
+        for symbol in symbols {
+            state = self.next_state(state, symbol);
+            if self.is_dead(state) {
+                return None;
+            }
+        }
+
+        Some(state)
+    }
+
+    #[inline]
+    fn next_class(&self, state: u32, class: u32) -> u32 {
+        self.table[state as usize * self.stride + class as usize]
+    }
+
+    #[inline]
+    fn class_of(&self, code: u32) -> u32 {
+        match self.byte_classes.get(code as usize) {
+            Some(&class) => class,
+            None => self.search_class(code),
+        }
+    }
+
+    #[inline]
+    fn search_class(&self, code: u32) -> u32 {
+        let index = self.classes.partition_point(|&(start, _)| start <= code);
+        self.classes[index - 1].1
+    }
+}
+
+impl From<&DFA> for DenseDFA {
+    #[inline]
+    fn from(dfa: &DFA) -> Self {
+        Self::from_dfa(dfa)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fsa::dense::DenseDFA;
+    use crate::fsa::{CompileError, Symbol};
+    use crate::regex::{parse, Regex};
+
+    #[test]
+    fn agrees_with_dfa() {
+        let dfa = parse("[a-c]x|[α-ω]+y|中|ÿ").unwrap().as_nfa().as_dfa();
+        let dense = DenseDFA::from_dfa(&dfa);
+        for string in [
+            "", "ax", "cx", "dx", "a", "αy", "αωy", "ωωωy", "y", "中", "中中", "ÿ", "þ", "Ā",
+        ] {
+            let symbols = || string.chars().map(Symbol::Char);
+            let expected = dfa
+                .end_of(symbols())
+                .is_some_and(|s| dfa.state(s).acceptable());
+            let accepted = dense.end_of(symbols()).is_some_and(|s| dense.acceptable(s));
+            assert_eq!(accepted, expected, "{string}");
+        }
+    }
+
+    #[test]
+    fn equivalence_classes() {
+        // [a-z] 为一类，其余码点（含区间之间的空隙）均转入死状态，归为另一类
+        let dfa = parse("[a-z]+").unwrap().as_nfa().as_dfa().minimize();
+        let dense = DenseDFA::from_dfa(&dfa);
+        assert_eq!(dense.stride(), 2);
+        assert_eq!(dense.state_count(), 3);
+
+        let start = dense.start();
+        let next = dense.next_state(start, Symbol::Char('a'));
+        assert_eq!(dense.next_state(start, Symbol::Char('z')), next);
+        assert_eq!(dense.next_byte(start, b'm'), next);
+        assert_eq!(dense.next_state(next, Symbol::Range('b', 'y')), next);
+        assert!(dense.acceptable(next) && !dense.acceptable(start));
+    }
+
+    #[test]
+    fn dead_state() {
+        let dfa = parse("ab").unwrap().as_nfa().as_dfa();
+        let dense = DenseDFA::from_dfa(&dfa);
+        let dead = dense.next_state(dense.start(), Symbol::Char('b'));
+        assert!(dense.is_dead(dead) && !dense.acceptable(dead));
+        for symbol in ['a', 'b', '中'].map(Symbol::Char) {
+            assert_eq!(dense.next_state(dead, symbol), DenseDFA::DEAD);
+        }
+        assert_eq!(dense.end_of("ba".chars().map(Symbol::Char)), None);
+        assert!(dense.end_of("ab".chars().map(Symbol::Char)).is_some());
+    }
+
+    #[test]
+    fn memory_limit() {
+        let dfa = parse("(a|b)*a(a|b)(a|b)").unwrap().as_nfa().as_dfa();
+        let used = DenseDFA::from_dfa(&dfa).memory_usage();
+        assert!(DenseDFA::from_dfa_with_limit(&dfa, used).is_ok());
+
+        // 转移表本身超出上限时在分配之前拒绝，否则按总占用拒绝
+        for limit in [0, used / 4, used - 1] {
+            match DenseDFA::from_dfa_with_limit(&dfa, limit) {
+                Err(CompileError::TooMuchMemory {
+                    used: needed,
+                    limit: l,
+                }) => {
+                    assert_eq!(l, limit);
+                    assert!(limit < needed && needed <= used);
+                }
+                other => panic!("应超出内存上限：{other:?}"),
+            }
+        }
+    }
+}
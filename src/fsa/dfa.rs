@@ -1,139 +1,702 @@
-use crate::fsa::{self, StateID, Symbol};
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet, LinkedList};
+use crate::fsa::biguint::BigUint;
+use crate::fsa::nfa::NFA;
+use crate::fsa::{self, CompileError, RuleSet, StateID, StateSet, Symbol};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, LinkedList};
 use std::fmt::{Debug, Formatter};
-use std::rc::Rc;
+use std::ops::RangeInclusive;
 
+/// DFA，所有状态存放于自身的状态表中，以 `StateID` 下标互相引用
+#[derive(Clone)]
 pub struct DFA {
-    start: StateNode,
+    states: Vec<State>,
+    start: StateID,
+    /// 是否为字节模式，见 `NFA::into_bytes`
+    bytes: bool,
 }
 
 impl DFA {
+    /// 构建仅含始态的 DFA
     #[inline]
-    pub fn new(start: StateNode) -> Self {
-        Self { start }
+    pub fn new(acceptable: bool) -> Self {
+        Self {
+            states: vec![State::new(acceptable)],
+            start: StateID(0),
+            bytes: false,
+        }
+    }
+
+    #[inline]
+    pub fn start(&self) -> StateID {
+        self.start
+    }
+
+    /// 是否为字节模式，即由字节模式的 NFA 确定化而来，见 `NFA::into_bytes`
+    #[inline]
+    pub fn is_bytes(&self) -> bool {
+        self.bytes
+    }
+
+    #[inline]
+    pub(crate) fn set_bytes(&mut self, bytes: bool) {
+        self.bytes = bytes;
+    }
+
+    #[inline]
+    pub fn state(&self, id: StateID) -> &State {
+        &self.states[id.0]
+    }
+
+    /// 所有状态（含不可达状态），按 ID 升序排列
+    #[inline]
+    pub fn states(&self) -> impl Iterator<Item = (StateID, &State)> + '_ {
+        self.states.iter().enumerate().map(|(i, s)| (StateID(i), s))
+    }
+
+    /// 状态数目
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// 所接受的语言是否为空，即没有可达的终态
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        !self
+            .all_states()
+            .into_iter()
+            .any(|id| self.state(id).acceptable())
+    }
+
+    /// 新增状态，终态接受规则 0
+    #[inline]
+    pub fn add_state(&mut self, acceptable: bool) -> StateID {
+        self.states.push(State::new(acceptable));
+        StateID(self.states.len() - 1)
+    }
+
+    /// 设置状态所接受的规则集，空集表示非终态
+    #[inline]
+    pub fn set_rules(&mut self, id: StateID, rules: RuleSet) -> &mut Self {
+        self.states[id.0].rules = rules;
+        self
+    }
+
+    /// 转移，符号区间不可与已有转移重叠；与目标相同的相邻区间合并
+    #[inline]
+    pub fn transition(&mut self, from: StateID, symbol: Symbol, to: StateID) -> &mut Self {
+        self.states[from.0].transition(symbol, to);
+        self
     }
 
     /// 根据符号序列获取最终可达状态
-    pub fn end_of(&self, symbols: impl IntoIterator<Item = Symbol>) -> Option<StateNode> {
-        let mut state = self.start.clone();
+    pub fn end_of(&self, symbols: impl IntoIterator<Item = Symbol>) -> Option<StateID> {
+        let mut state = self.start;
 
         for symbol in symbols {
-            let new_state = if let Some(state) = state.borrow().next_state(symbol) {
-                state
-            } else {
-                return None;
-            };
-            state = new_state;
+            state = self.state(state).next_state(symbol)?;
         }
 
         Some(state)
     }
 
-    /// 最小化
+    /// 最小化：Hopcroft 划分细化算法，时间复杂度 O(m·log n)，m 为按基本区间拆分后的转移数目
+    ///
+    /// 先删去不可达及无法到达终态的状态（缺失的转移即指向隐含的死状态），再按接受的规则集初始划分；
+    /// 此后以工作表中的块为分割者反复细化，被分割的块不在工作表中时只需加入较小的一半
     pub fn minimize(&self) -> DFA {
-        let mut group = self.all_states().divide_by_acceptable();
+        let states = self.useful_states();
+        if !states.contains(&self.start) {
+            let mut dfa = DFA::new(false);
+            dfa.bytes = self.bytes;
+            return dfa;
+        }
+        let index = states
+            .iter()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect::<HashMap<_, _>>();
+
+        // 逆向转移：`inverse[t]` 为所有 (基本区间下标, 源状态)，源状态经该区间到达 `t`
+        let symbols = fsa::alphabet(states.iter().map(|&id| self.state(id)))
+            .into_iter()
+            .filter_map(|symbol| symbol.range())
+            .collect::<Vec<_>>();
+        let mut inverse = vec![Vec::new(); states.len()];
+        for (source, &id) in states.iter().enumerate() {
+            for (symbol, target) in self.state(id).transitions() {
+                let (Some(&target), Some((start, end))) = (index.get(&target), symbol.range())
+                else {
+                    continue;
+                };
+                let first = symbols.partition_point(|&(s, _)| s < start);
+                for (i, _) in symbols[first..]
+                    .iter()
+                    .enumerate()
+                    .take_while(|(_, &(s, _))| s <= end)
+                {
+                    inverse[target].push((first + i, source));
+                }
+            }
+        }
+
+        let mut blocks = HashMap::<_, Vec<_>>::new();
+        for (i, &id) in states.iter().enumerate() {
+            blocks.entry(&self.state(id).rules).or_default().push(i);
+        }
+        let mut partition = Partition::new(states.len(), blocks.into_values());
 
-        loop {
-            let group_copy = group.clone();
-            for set in group_copy.iter() {
-                group.remove(&set);
-                group.extend(set.divide(&group_copy).into_iter())
+        let mut worklist = (0..partition.len()).collect::<Vec<_>>();
+        let mut in_worklist = vec![true; partition.len()];
+        while let Some(splitter) = worklist.pop() {
+            in_worklist[splitter] = false;
+
+            let mut predecessors = partition
+                .elements(splitter)
+                .iter()
+                .flat_map(|&target| inverse[target].iter().copied())
+                .collect::<Vec<_>>();
+            predecessors.sort_unstable();
+
+            for group in predecessors.chunk_by(|a, b| a.0 == b.0) {
+                for &(_, source) in group {
+                    partition.mark(source);
+                }
+                for (old, new) in partition.split() {
+                    in_worklist.push(false);
+                    let block = if in_worklist[old] || partition.size(new) <= partition.size(old) {
+                        new
+                    } else {
+                        old
+                    };
+                    if !in_worklist[block] {
+                        in_worklist[block] = true;
+                        worklist.push(block);
+                    }
+                }
             }
-            if group.len() == group_copy.len() {
+        }
+
+        self.merge(
+            (0..partition.len())
+                .map(|block| {
+                    partition
+                        .elements(block)
+                        .iter()
+                        .map(|&i| states[i])
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Brzozowski 最小化：反转并确定化两次，即得到最小 DFA
+    ///
+    /// 最坏情况下中间结果的状态数目呈指数级，主要用于与 `minimize` 相互校验；
+    /// 只保留是否接受，所有终态统一接受规则 0
+    #[inline]
+    pub fn minimize_brzozowski(&self) -> DFA {
+        self.reverse().as_dfa().reverse().as_dfa()
+    }
+
+    /// 反转：所有转移反向，始态与终态互换，所接受的语言为原语言中各串的逆序
+    ///
+    /// 终态可能有多个，反转后的始态经 ε 转移到达它们，因此结果为 NFA，见 `NFA::reverse`
+    #[inline]
+    pub fn reverse(&self) -> NFA {
+        self.as_nfa().reverse()
+    }
+
+    /// 补全：对于字母表中每个状态缺失的转移，补上指向新增死状态的转移
+    ///
+    /// 字母表以符号区间给出，可重叠；已有的转移（含字母表之外的）保持不变
+    pub fn complete(&self, alphabet: impl IntoIterator<Item = Symbol>) -> DFA {
+        let alphabet = fsa::split_symbols(alphabet)
+            .into_iter()
+            .filter_map(|symbol| symbol.range())
+            .collect::<Vec<_>>();
+
+        let mut dfa = self.clone();
+        let mut dead = None;
+        for (id, state) in self.states() {
+            for &(start, end) in &alphabet {
+                for (gap_start, gap_end) in state.gaps(start, end) {
+                    let dead = *dead.get_or_insert_with(|| dfa.add_state(false));
+                    dfa.transition(id, Symbol::Range(gap_start, gap_end), dead);
+                }
+            }
+        }
+        if let Some(dead) = dead {
+            for &(start, end) in &alphabet {
+                dfa.transition(dead, Symbol::Range(start, end), dead);
+            }
+        }
+
+        dfa
+    }
+
+    /// 补：以全体 Unicode 标量值为字母表补全后，终态与非终态互换
+    ///
+    /// 所接受的语言为原语言之外的所有字符串，原有的规则编号不再保留，终态统一接受规则 0
+    pub fn complement(&self) -> DFA {
+        let mut dfa = self.complete([Symbol::Range('\0', char::MAX)]);
+        for state in &mut dfa.states {
+            state.rules = RuleSet::from_iter((!state.acceptable()).then_some(0));
+        }
+        dfa
+    }
+
+    /// 交：接受同时被两者接受的字符串
+    #[inline]
+    pub fn intersect(&self, other: &DFA) -> DFA {
+        self.intersect_with_limit(other, usize::MAX).unwrap()
+    }
+
+    /// 交，积的状态数目超过 `max_states` 时放弃构造并返回错误
+    #[inline]
+    pub(crate) fn intersect_with_limit(
+        &self,
+        other: &DFA,
+        max_states: usize,
+    ) -> Result<DFA, CompileError> {
+        self.product(other, |a, b| a && b, max_states)
+    }
+
+    /// 并：接受被任一者接受的字符串
+    #[inline]
+    pub fn union(&self, other: &DFA) -> DFA {
+        self.product(other, |a, b| a || b, usize::MAX).unwrap()
+    }
+
+    /// 差：接受被自身接受而不被 `other` 接受的字符串
+    #[inline]
+    pub fn difference(&self, other: &DFA) -> DFA {
+        self.difference_with_limit(other, usize::MAX).unwrap()
+    }
+
+    /// 差，积的状态数目超过 `max_states` 时放弃构造并返回错误
+    #[inline]
+    pub(crate) fn difference_with_limit(
+        &self,
+        other: &DFA,
+        max_states: usize,
+    ) -> Result<DFA, CompileError> {
+        self.product(other, |a, b| a && !b, max_states)
+    }
+
+    /// 判断两者所接受的语言是否相同，不同时返回最短的区分串（同样长度下字典序最小），
+    /// 即恰被其中一者接受的字符串
+    ///
+    /// 在两者的积上自始态广度优先搜索接受情况不同的二元组；字节模式下区分串的每个字符对应一个字节
+    #[inline]
+    pub fn equivalent(&self, other: &DFA) -> Result<(), String> {
+        let product = self.product(other, |a, b| a != b, usize::MAX).unwrap();
+        match product.shortest() {
+            Some(string) => Err(string),
+            None => Ok(()),
+        }
+    }
+
+    /// 判断是否接受字母表上的所有字符串，不是时返回最短的不被接受的串
+    ///
+    /// 字母表以符号区间给出，可重叠
+    pub fn is_universal(&self, alphabet: impl IntoIterator<Item = Symbol>) -> Result<(), String> {
+        let mut universe = DFA::new(true);
+        universe.bytes = self.bytes;
+        for symbol in fsa::split_symbols(alphabet) {
+            universe.transition(universe.start(), symbol, universe.start());
+        }
+        universe.is_subset_of(self)
+    }
+
+    /// 判断所接受的语言是否包含于 `other` 所接受的语言，不是时返回最短的见证串，
+    /// 即被自身接受而不被 `other` 接受的字符串
+    #[inline]
+    pub fn is_subset_of(&self, other: &DFA) -> Result<(), String> {
+        match self.difference(other).shortest() {
+            Some(string) => Err(string),
+            None => Ok(()),
+        }
+    }
+
+    /// 两者所接受的语言是否相交，相交时返回交集中最短的字符串
+    #[inline]
+    pub fn overlaps(&self, other: &DFA) -> Option<String> {
+        self.intersect(other).shortest()
+    }
+
+    /// 按短字典序（先按长度，同样长度下按字典序）依次产生所接受的字符串，见 `Strings`
+    #[inline]
+    pub fn strings(&self) -> Strings<'_> {
+        Strings::new(self)
+    }
+
+    /// 长度（字符数目）恰为 `n` 的被接受字符串的数目
+    ///
+    /// 区间转移按其中的字符数目计入，字节模式下即为字节串的数目
+    #[inline]
+    pub fn count_of_length(&self, n: usize) -> BigUint {
+        let mut counts = self.start_counts();
+        for _ in 0..n {
+            if counts.iter().all(BigUint::is_zero) {
                 break;
             }
+            counts = self.next_counts(&counts);
         }
+        self.accepted_count(&counts)
+    }
 
-        self.merge(group)
+    /// 长度不超过 `n` 的被接受字符串的数目
+    pub fn count_up_to(&self, n: usize) -> BigUint {
+        let mut counts = self.start_counts();
+        let mut total = self.accepted_count(&counts);
+        for _ in 0..n {
+            counts = self.next_counts(&counts);
+            if counts.iter().all(BigUint::is_zero) {
+                break;
+            }
+            total += &self.accepted_count(&counts);
+        }
+        total
+    }
+
+    /// 所接受的语言是否有限，即可达且能够到达终态的状态之间不存在环
+    #[inline]
+    pub fn is_finite(&self) -> bool {
+        self.useful_order().is_some()
+    }
+
+    /// 语言有限时最长的被接受字符串的长度，语言无限或为空时返回 `None`
+    pub fn max_length(&self) -> Option<usize> {
+        let order = self.useful_order()?;
+
+        // 按拓扑序逆序计算各状态到终态的最长路径
+        let mut longest = HashMap::new();
+        for &id in order.iter().rev() {
+            let state = self.state(id);
+            let length = state
+                .next_states()
+                .filter_map(|target| longest.get(&target).map(|&length| length + 1))
+                .chain(state.acceptable().then_some(0))
+                .max();
+            if let Some(length) = length {
+                longest.insert(id, length);
+            }
+        }
+
+        longest.get(&self.start).copied()
+    }
+
+    /// 按长度逐步动态规划的初始一步：读入 0 个字符后到达各状态的字符串数目，仅始态为 1
+    ///
+    /// 每一步只依赖前一步，计数时只保留当前一步，内存与状态数目及数目的位数成正比
+    fn start_counts(&self) -> Vec<BigUint> {
+        let mut counts = vec![BigUint::zero(); self.len()];
+        counts[self.start.0] = BigUint::from(1u64);
+        counts
+    }
+
+    /// 再读入 1 个字符后到达各状态的字符串数目
+    fn next_counts(&self, counts: &[BigUint]) -> Vec<BigUint> {
+        let mut next = vec![BigUint::zero(); self.len()];
+        for (id, state) in self.states() {
+            if counts[id.0].is_zero() {
+                continue;
+            }
+            for (symbol, target) in state.transitions() {
+                let (start, end) = symbol.range().unwrap();
+                next[target.0] += &(&counts[id.0] * char_count(start, end));
+            }
+        }
+        next
+    }
+
+    /// 到达终态的字符串数目之和
+    fn accepted_count(&self, counts: &[BigUint]) -> BigUint {
+        self.states()
+            .filter(|(_, state)| state.acceptable())
+            .map(|(id, _)| &counts[id.0])
+            .sum()
+    }
+
+    /// 可达且能够到达终态的状态的拓扑序，其间存在环时返回 `None`
+    fn useful_order(&self) -> Option<Vec<StateID>> {
+        let useful = self.useful_states().into_iter().collect::<StateSet>();
+
+        let mut in_degrees = useful
+            .iter()
+            .map(|&id| (id, 0))
+            .collect::<HashMap<_, usize>>();
+        for &id in &useful {
+            for target in self.state(id).next_states() {
+                if let Some(degree) = in_degrees.get_mut(&target) {
+                    *degree += 1;
+                }
+            }
+        }
+
+        let mut queue = in_degrees
+            .iter()
+            .filter(|&(_, &degree)| degree == 0)
+            .map(|(&id, _)| id)
+            .collect::<LinkedList<_>>();
+        let mut order = Vec::with_capacity(useful.len());
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+            for target in self.state(id).next_states() {
+                if let Some(degree) = in_degrees.get_mut(&target) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        queue.push_back(target);
+                    }
+                }
+            }
+        }
+
+        (order.len() == useful.len()).then_some(order)
+    }
+
+    /// 积构造：状态为两者状态的二元组，`accept` 由两侧是否接受决定二元组是否接受
+    ///
+    /// 缺失的转移视为进入隐含的死状态（以 `None` 表示），无论此后如何都不可能接受的二元组不予构建；
+    /// 原有的规则编号不再保留，终态统一接受规则 0；状态数目将超过 `max_states` 时返回错误
+    fn product(
+        &self,
+        other: &DFA,
+        accept: impl Fn(bool, bool) -> bool,
+        max_states: usize,
+    ) -> Result<DFA, CompileError> {
+        type Pair = (Option<StateID>, Option<StateID>);
+
+        let acceptable =
+            |id: Option<StateID>, dfa: &DFA| id.is_some_and(|id| dfa.state(id).acceptable());
+        // 死状态一侧恒不接受，另一侧取遍两种可能仍不接受时，二元组即为死状态
+        let possible = |id: Option<StateID>| {
+            if id.is_some() {
+                &[false, true][..]
+            } else {
+                &[false][..]
+            }
+        };
+        let is_dead = |(a, b): Pair| {
+            possible(a)
+                .iter()
+                .all(|&x| possible(b).iter().all(|&y| !accept(x, y)))
+        };
+
+        CompileError::check_states(0, max_states)?;
+        let start = (Some(self.start), Some(other.start));
+        let mut dfa = DFA::new(accept(
+            acceptable(start.0, self),
+            acceptable(start.1, other),
+        ));
+        dfa.bytes = self.bytes || other.bytes;
+        if is_dead(start) {
+            return Ok(dfa);
+        }
+
+        let mut dfa_states = HashMap::from([(start, dfa.start())]);
+        let mut queue = LinkedList::from([start]);
+        while let Some(pair @ (a, b)) = queue.pop_front() {
+            let transitions = |id: Option<StateID>, dfa: &DFA| {
+                id.into_iter()
+                    .flat_map(|id| dfa.state(id).transitions().map(|(symbol, _)| symbol))
+                    .collect::<Vec<_>>()
+            };
+            let symbols = transitions(a, self)
+                .into_iter()
+                .chain(transitions(b, other));
+            for symbol in fsa::split_symbols(symbols) {
+                let next = (
+                    a.and_then(|id| self.state(id).next_state(symbol)),
+                    b.and_then(|id| other.state(id).next_state(symbol)),
+                );
+                if is_dead(next) {
+                    continue;
+                }
+
+                let dfa_state =
+                    match dfa_states.entry(next) {
+                        Entry::Occupied(entry) => *entry.get(),
+                        Entry::Vacant(entry) => {
+                            CompileError::check_states(dfa.len(), max_states)?;
+                            queue.push_back(next);
+                            *entry.insert(dfa.add_state(accept(
+                                acceptable(next.0, self),
+                                acceptable(next.1, other),
+                            )))
+                        }
+                    };
+                dfa.transition(dfa_states[&pair], symbol, dfa_state);
+            }
+        }
+
+        Ok(dfa)
+    }
+
+    /// 最短的被接受字符串，同样长度下字典序最小，每个转移取区间起点
+    ///
+    /// 广度优先搜索时按区间升序扩展，队列中的路径即按长度、字典序排列，首个出队的终态对应所求的串
+    fn shortest(&self) -> Option<String> {
+        let mut parents = HashMap::from([(self.start, None)]);
+        let mut queue = LinkedList::from([self.start]);
+
+        while let Some(id) = queue.pop_front() {
+            if self.state(id).acceptable() {
+                let mut chars = Vec::new();
+                let mut current = id;
+                while let Some((parent, char)) = parents[&current] {
+                    chars.push(char);
+                    current = parent;
+                }
+                return Some(chars.into_iter().rev().collect());
+            }
+
+            for (symbol, target) in self.state(id).transitions() {
+                if let Entry::Vacant(entry) = parents.entry(target) {
+                    let (start, _) = symbol.range().unwrap();
+                    entry.insert(Some((id, start)));
+                    queue.push_back(target);
+                }
+            }
+        }
+
+        None
     }
 
-    /// 获取所有的状态集
+    /// 转换为等价的 NFA，所有终态经 ε 转移汇入唯一的新终态，字节模式保持不变
+    pub fn as_nfa(&self) -> NFA {
+        let mut nfa = NFA::new();
+        nfa.set_bytes(self.bytes);
+        let end = nfa.end();
+
+        // NFA 自带的始态、终态之后依次对应 DFA 的各个状态
+        let offset = nfa.len();
+        for _ in &self.states {
+            nfa.add_state();
+        }
+        for (id, state) in self.states() {
+            let nfa_state = id.offset(offset);
+            for (symbol, next_state) in state.transitions() {
+                nfa.transition(nfa_state, symbol, next_state.offset(offset));
+            }
+            if state.acceptable() {
+                nfa.e_transition(nfa_state, end);
+            }
+        }
+
+        nfa.set_start(self.start.offset(offset));
+        nfa
+    }
+
+    /// 获取所有可达的状态集
     fn all_states(&self) -> StateSet {
         let mut set = StateSet::new();
-        let mut queue = LinkedList::from([self.start.clone()]);
+        let mut queue = LinkedList::from([self.start]);
 
-        while let Some(state) = queue.pop_front() {
-            if set.contains(&state.borrow()) {
+        while let Some(id) = queue.pop_front() {
+            if !set.insert(id) {
                 continue;
             }
-            queue.extend(state.borrow().transitions.values().cloned());
-            set.insert(state);
+            queue.extend(self.state(id).next_states());
         }
 
         set
     }
 
-    /// 将状态集组合并，构建新的 DFA
-    fn merge(&self, group: StateSetGroup) -> DFA {
-        let mut typical_states = HashMap::new();
+    /// 可达且能够到达终态的所有状态，按 ID 升序排列
+    fn useful_states(&self) -> Vec<StateID> {
+        let reachable = self.all_states();
 
-        for set in group.iter() {
-            let typical_state = Self::typical_state_of_set(set, &mut typical_states);
-            for state in set.states() {
-                let mut transitions = state.borrow().transitions.clone();
-                for next_state in transitions.values_mut() {
-                    *next_state =
-                        Self::typical_state_of_state(next_state, &group, &mut typical_states);
-                }
-                typical_state.borrow_mut().transitions.extend(transitions);
+        let mut predecessors = HashMap::<_, Vec<_>>::new();
+        for &id in &reachable {
+            for target in self.state(id).next_states() {
+                predecessors.entry(target).or_default().push(id);
             }
         }
 
-        let start = Self::typical_state_of_state(&self.start, &group, &mut typical_states);
-        DFA::new(start)
-    }
+        let mut useful = StateSet::new();
+        let mut queue = reachable
+            .iter()
+            .copied()
+            .filter(|&id| self.state(id).acceptable())
+            .collect::<LinkedList<_>>();
+        while let Some(id) = queue.pop_front() {
+            if !useful.insert(id) {
+                continue;
+            }
+            queue.extend(predecessors.get(&id).into_iter().flatten());
+        }
 
-    #[inline]
-    fn typical_state_of_state<'a>(
-        state: &StateNode,
-        group: &'a StateSetGroup,
-        typical_states: &mut HashMap<&'a StateSet, StateNode>,
-    ) -> StateNode {
-        let set = group.iter().find(|s| s.contains(&state.borrow())).unwrap();
-        Self::typical_state_of_set(set, typical_states).clone()
+        useful.into_iter().collect()
     }
 
-    #[inline]
-    fn typical_state_of_set<'a>(
-        set: &'a StateSet,
-        typical_states: &mut HashMap<&'a StateSet, StateNode>,
-    ) -> StateNode {
-        typical_states
-            .entry(set)
-            .or_insert(State::new_node(set.states().any(|s| s.borrow().acceptable)))
-            .clone()
+    /// 将等价状态块合并，构建新的 DFA；指向块外（无用状态）的转移被丢弃
+    fn merge(&self, blocks: Vec<Vec<StateID>>) -> DFA {
+        let block_of = blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(i, block)| block.iter().map(move |&id| (id, i)))
+            .collect::<HashMap<_, _>>();
+
+        // 始态所在的块成为新 DFA 的始态
+        let start_block = block_of[&self.start];
+        let mut dfa = DFA::new(false);
+        dfa.bytes = self.bytes;
+        let typical_states = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let id = if i == start_block {
+                    dfa.start()
+                } else {
+                    dfa.add_state(false)
+                };
+                // 同块状态接受的规则集必定相同
+                dfa.set_rules(id, self.state(block[0]).rules.clone());
+                id
+            })
+            .collect::<Vec<_>>();
+
+        for (i, block) in blocks.iter().enumerate() {
+            // 同块状态在块的层面上行为一致，取任一状态的转移即可
+            for (symbol, next_state) in self.state(block[0]).transitions() {
+                if let Some(&next_block) = block_of.get(&next_state) {
+                    dfa.transition(typical_states[i], symbol, typical_states[next_block]);
+                }
+            }
+        }
+
+        dfa
     }
 }
 
 impl Debug for DFA {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        fn state_str(state: &State) -> String {
-            if state.acceptable {
-                format!("[{:?}]", state.id)
+        let state_str = |id: StateID| {
+            if self.state(id).acceptable() {
+                format!("[{:?}]", id)
             } else {
-                format!("({:?})", state.id)
+                format!("({:?})", id)
             }
-        }
+        };
 
-        let mut ids = HashSet::new();
-        let mut queue = LinkedList::from([self.start.clone()]);
-        while let Some(state) = queue.pop_front() {
-            let id = state.borrow().id;
-            if ids.contains(&id) {
+        let mut ids = StateSet::new();
+        let mut queue = LinkedList::from([self.start]);
+        while let Some(id) = queue.pop_front() {
+            if !ids.insert(id) {
                 continue;
             }
-            ids.insert(id);
 
-            for (symbol, next_state) in &state.borrow().transitions {
-                queue.push_back(next_state.clone());
+            for (symbol, next_state) in self.state(id).transitions() {
+                queue.push_back(next_state);
 
-                write!(
+                writeln!(
                     f,
-                    "[{}, {:?}] = {}\n",
-                    state_str(&state.borrow()),
+                    "[{}, {:?}] = {}",
+                    state_str(id),
                     symbol,
-                    state_str(&next_state.borrow())
+                    state_str(next_state)
                 )?;
             }
         }
@@ -141,102 +704,1046 @@ impl Debug for DFA {
     }
 }
 
-pub type StateNode = fsa::StateNode<State>;
+/// 区间 `[start, end]` 中 Unicode 标量值的数目（不含代理区）
+#[inline]
+fn char_count(start: char, end: char) -> u64 {
+    let count = end as u64 - start as u64 + 1;
+    if start <= '\u{D7FF}' && end >= '\u{E000}' {
+        count - 0x800
+    } else {
+        count
+    }
+}
 
-/// DFA 状态
-#[derive(Debug)]
-pub struct State {
-    id: StateID,
-    transitions: HashMap<Symbol, StateNode>,
-    acceptable: bool,
+/// 区间 `[start, end]` 中的第 `index` 个 Unicode 标量值（跳过代理区）
+#[inline]
+fn nth_char(start: char, index: u64) -> char {
+    let mut code = start as u64 + index;
+    if start <= '\u{D7FF}' && code > 0xD7FF {
+        code += 0x800;
+    }
+    char::from_u32(code as u32).unwrap()
 }
 
-impl fsa::State for State {
+/// 随机数源，由使用者提供，见 `Sampler`
+pub trait Rng {
+    /// 均匀分布的 64 位随机数
+    fn next_u64(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> Rng for F {
     #[inline]
-    fn id(&self) -> StateID {
-        self.id
+    fn next_u64(&mut self) -> u64 {
+        self()
+    }
+}
+
+/// 在给定长度范围内，从 DFA 所接受的字符串中均匀随机抽取
+///
+/// 先最小化，再统计各状态恰以 `k` 个字符到达终态的路径数目；抽取时先按各长度的字符串数目选定长度，
+/// 此后每步按转移区间的字符数目与目标状态的剩余路径数目之积选择转移，再在区间内均匀选择字符，
+/// 每个字符串被抽中的概率相同。字节模式下每个字符对应一个字节
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    dfa: DFA,
+    lengths: RangeInclusive<usize>,
+    /// 第 `k` 项的第 `i` 个元素为状态 `i` 恰以 `k` 个字符到达终态的路径数目
+    paths: Vec<Vec<BigUint>>,
+    /// 范围内被接受字符串的总数
+    count: BigUint,
+}
+
+impl Sampler {
+    /// 长度上界的最大值：路径数目的位数随长度增长，统计的时间与内存约与状态数目及长度上界的平方之积成正比
+    pub const MAX_LENGTH: usize = 1024;
+
+    /// 统计长度不超过 `lengths.end()` 的路径数目
+    ///
+    /// 语言有限时长度上界截断为 `max_length()`，因此可以传入 `0..=usize::MAX`；
+    /// 截断后的上界仍超过 `MAX_LENGTH` 时（如语言无限而范围无界）返回 `None`
+    pub fn new(dfa: &DFA, lengths: RangeInclusive<usize>) -> Option<Self> {
+        let dfa = dfa.minimize();
+        let lengths = match dfa.is_finite() {
+            true => *lengths.start()..=(*lengths.end()).min(dfa.max_length().unwrap_or(0)),
+            false => lengths,
+        };
+        if *lengths.end() > Self::MAX_LENGTH {
+            return None;
+        }
+
+        let mut paths = vec![dfa
+            .states()
+            .map(|(_, state)| BigUint::from(state.acceptable() as u64))
+            .collect::<Vec<_>>()];
+        while paths.len() <= *lengths.end() {
+            let last = paths.last().unwrap();
+            let next = dfa
+                .states()
+                .map(|(_, state)| {
+                    state
+                        .transitions()
+                        .map(|(symbol, target)| {
+                            let (start, end) = symbol.range().unwrap();
+                            &last[target.0] * char_count(start, end)
+                        })
+                        .fold(BigUint::zero(), |sum, n| sum + &n)
+                })
+                .collect();
+            paths.push(next);
+        }
+
+        let start = dfa.start.0;
+        let count = paths
+            .get(lengths.clone())
+            .into_iter()
+            .flatten()
+            .map(|paths| &paths[start])
+            .sum();
+        Some(Self {
+            dfa,
+            lengths,
+            paths,
+            count,
+        })
     }
 
+    /// 范围内被接受字符串的总数
+    #[inline]
+    pub fn count(&self) -> &BigUint {
+        &self.count
+    }
+
+    /// 均匀随机抽取一个字符串，范围内没有被接受的字符串时返回 `None`
+    pub fn sample(&self, rng: &mut impl Rng) -> Option<String> {
+        if self.count.is_zero() {
+            return None;
+        }
+
+        let start = self.dfa.start;
+        let index = random_below(&self.count, rng);
+        let mut cumulative = BigUint::zero();
+        let len = self.lengths.clone().find(|&len| {
+            cumulative += &self.paths[len][start.0];
+            index < cumulative
+        })?;
+
+        let mut string = String::with_capacity(len);
+        let mut state = start;
+        for remaining in (0..len).rev() {
+            // 转移的权重为区间内的字符数目与目标状态剩余路径数目之积
+            let index = random_below(&self.paths[remaining + 1][state.0], rng);
+            let mut cumulative = BigUint::zero();
+            let (start, end, target) = self
+                .dfa
+                .state(state)
+                .transitions()
+                .map(|(symbol, target)| {
+                    let (start, end) = symbol.range().unwrap();
+                    (start, end, target)
+                })
+                .find(|&(start, end, target)| {
+                    cumulative += &(&self.paths[remaining][target.0] * char_count(start, end));
+                    index < cumulative
+                })?;
+
+            let offset = random_below(&BigUint::from(char_count(start, end)), rng);
+            string.push(nth_char(start, offset.to_u64().unwrap()));
+            state = target;
+        }
+
+        Some(string)
+    }
+}
+
+/// 均匀分布于 `[0, bound)` 的随机数，`bound` 不可为零
+///
+/// 生成与 `bound` 位数相同的随机数，不小于 `bound` 时重新生成，期望不超过两次
+fn random_below(bound: &BigUint, rng: &mut impl Rng) -> BigUint {
+    let bits = bound.bits();
+    let len = bits.div_ceil(32) as usize;
+    loop {
+        let mut limbs = (0..len.div_ceil(2))
+            .flat_map(|_| {
+                let n = rng.next_u64();
+                [n as u32, (n >> 32) as u32]
+            })
+            .take(len)
+            .collect::<Vec<_>>();
+        if !bits.is_multiple_of(32) {
+            *limbs.last_mut().unwrap() &= (1 << (bits % 32)) - 1;
+        }
+
+        let n = BigUint::from_limbs(limbs);
+        if n < *bound {
+            return n;
+        }
+    }
+}
+
+/// 所接受的字符串，按短字典序排列，见 `DFA::strings`
+///
+/// 逐个长度地深度优先产生：仅沿恰好还能以剩余步数到达终态的转移前进，每个字符串都可在 O(长度) 步内得到；
+/// 区间转移中的每个字符都会依次产生，字节模式下每个字符对应一个字节。语言有限时产生完所有字符串即结束
+pub struct Strings<'d> {
+    dfa: &'d DFA,
+    /// 所有可达的状态
+    reachable: StateSet,
+    /// 第 `k` 项为恰经 `k` 次转移即可到达终态的可达状态，按需计算；某项为空时此后各项均为空
+    live: Vec<StateSet>,
+    max_len: Option<usize>,
+    /// 当前产生的字符串长度
+    len: usize,
+    /// 当前长度是否已产生过字符串
+    started: bool,
+    /// 当前字符串的各字符及读入该字符后到达的状态
+    path: Vec<(char, StateID)>,
+    done: bool,
+}
+
+impl<'d> Strings<'d> {
+    fn new(dfa: &'d DFA) -> Self {
+        let reachable = dfa.all_states();
+        let accepting = reachable
+            .iter()
+            .copied()
+            .filter(|&id| dfa.state(id).acceptable())
+            .collect();
+        Self {
+            dfa,
+            reachable,
+            live: vec![accepting],
+            max_len: None,
+            len: 0,
+            started: false,
+            path: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// 只产生长度（字符数目）不超过 `len` 的字符串
+    #[inline]
+    pub fn max_len(mut self, len: usize) -> Self {
+        self.max_len = Some(len);
+        self
+    }
+
+    /// 恰经 `k` 次转移即可到达终态的可达状态
+    fn live(&mut self, k: usize) -> &StateSet {
+        while self.live.len() <= k {
+            let last = self.live.last().unwrap();
+            let next = self
+                .reachable
+                .iter()
+                .copied()
+                .filter(|&id| self.dfa.state(id).next_states().any(|t| last.contains(&t)))
+                .collect();
+            self.live.push(next);
+        }
+        &self.live[k]
+    }
+
+    /// 当前字符串读入后到达的状态
+    #[inline]
+    fn current(&self) -> StateID {
+        self.path.last().map_or(self.dfa.start, |&(_, id)| id)
+    }
+
+    /// 自 `state` 读入大于 `after` 的最小字符，且此后恰能以 `remaining - 1` 步到达终态
+    fn first_after(
+        &self,
+        state: StateID,
+        after: Option<char>,
+        remaining: usize,
+    ) -> Option<(char, StateID)> {
+        let from = match after {
+            Some(char) => fsa::next_char(char)?,
+            None => '\0',
+        };
+        let transitions = &self.dfa.state(state).transitions;
+        // 起点在 `from` 之前的区间可能覆盖 `from`
+        let first = transitions
+            .range(..=from)
+            .next_back()
+            .map_or(from, |(&start, _)| start);
+        transitions
+            .range(first..)
+            .find_map(|(&start, &(end, target))| {
+                let char = start.max(from);
+                (char <= end && self.live[remaining - 1].contains(&target))
+                    .then_some((char, target))
+            })
+    }
+
+    /// 自当前状态起每步取最小的字符，补齐到当前长度
+    fn descend(&mut self) {
+        while self.path.len() < self.len {
+            let remaining = self.len - self.path.len();
+            // 当前状态恰能以 `remaining` 步到达终态，必定存在这样的转移
+            let next = self.first_after(self.current(), None, remaining).unwrap();
+            self.path.push(next);
+        }
+    }
+
+    /// 变为同样长度下的下一个字符串，不存在时返回 `false`
+    fn advance(&mut self) -> bool {
+        while let Some((char, _)) = self.path.pop() {
+            let remaining = self.len - self.path.len();
+            if let Some(next) = self.first_after(self.current(), Some(char), remaining) {
+                self.path.push(next);
+                self.descend();
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for Strings<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.max_len.is_some_and(|max| self.len > max) || self.live(self.len).is_empty() {
+                self.done = true;
+                break;
+            }
+
+            let found = if self.started {
+                self.advance()
+            } else {
+                self.started = true;
+                self.live[self.len].contains(&self.dfa.start)
+            };
+            if found {
+                self.descend();
+                return Some(self.path.iter().map(|&(char, _)| char).collect());
+            }
+
+            self.len += 1;
+            self.started = false;
+        }
+        None
+    }
+}
+
+/// DFA 状态
+#[derive(Clone, Debug)]
+pub struct State {
+    /// 以区间起点为键，值为区间终点及目标状态，区间互不相交
+    transitions: BTreeMap<char, (char, StateID)>,
+    /// 接受的规则编号集，为空表示非终态
+    rules: RuleSet,
+}
+
+impl fsa::State for State {
     #[inline]
     fn alphabet(&self) -> Box<dyn Iterator<Item = Symbol> + '_> {
-        Box::new(self.transitions.keys().copied())
+        Box::new(self.transitions().map(|(symbol, _)| symbol))
     }
 }
 
 impl State {
     #[inline]
-    pub fn new(acceptable: bool) -> State {
+    fn new(acceptable: bool) -> State {
         State {
-            id: StateID::obtain(),
             transitions: Default::default(),
-            acceptable,
+            rules: acceptable.then_some(0).into_iter().collect(),
         }
     }
 
     #[inline]
     pub fn acceptable(&self) -> bool {
-        self.acceptable
+        !self.rules.is_empty()
+    }
+
+    /// 接受的规则编号，多条规则同时接受时取优先级最高（编号最小）者
+    #[inline]
+    pub fn rule(&self) -> Option<usize> {
+        self.rules.first().copied()
+    }
+
+    /// 接受的所有规则编号
+    #[inline]
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// 转移，符号区间不可与已有转移重叠；与目标相同的相邻区间合并
+    fn transition(&mut self, symbol: Symbol, target: StateID) {
+        let Some((mut start, mut end)) = symbol.range() else {
+            return;
+        };
+
+        let prev = self.transitions.range(..start).next_back();
+        if let Some((&prev_start, &(prev_end, prev_target))) = prev {
+            if fsa::next_char(prev_end) == Some(start) && prev_target == target {
+                self.transitions.remove(&prev_start);
+                start = prev_start;
+            }
+        }
+        if let Some(next) = fsa::next_char(end) {
+            if let Some(&(next_end, next_target)) = self.transitions.get(&next) {
+                if next_target == target {
+                    end = next_end;
+                    self.transitions.remove(&next);
+                }
+            }
+        }
+
+        self.transitions.insert(start, (end, target));
     }
 
+    /// 根据符号获取下一个状态，符号区间须完整落在某个转移区间内
     #[inline]
-    pub fn new_node(acceptable: bool) -> StateNode {
-        Rc::new(RefCell::new(State::new(acceptable)))
+    pub fn next_state(&self, symbol: Symbol) -> Option<StateID> {
+        let (start, end) = symbol.range()?;
+        let (_, &(range_end, target)) = self.transitions.range(..=start).next_back()?;
+        (range_end >= end).then_some(target)
     }
 
+    /// 所有转移，按区间升序排列
     #[inline]
-    pub fn transition(&mut self, symbol: Symbol, target: StateNode) {
-        self.transitions.insert(symbol, target);
+    pub fn transitions(&self) -> impl Iterator<Item = (Symbol, StateID)> + '_ {
+        self.transitions
+            .iter()
+            .map(|(&start, &(end, target))| (Symbol::Range(start, end), target))
     }
 
-    /// 根据符号获取下一个状态
+    /// 所有后继状态
     #[inline]
-    pub fn next_state(&self, symbol: Symbol) -> Option<StateNode> {
-        self.transitions.get(&symbol).cloned()
+    fn next_states(&self) -> impl Iterator<Item = StateID> + '_ {
+        self.transitions.values().map(|&(_, target)| target)
+    }
+
+    /// 区间 `[start, end]` 中没有转移的部分，按升序排列
+    fn gaps(&self, start: char, end: char) -> Vec<(char, char)> {
+        let mut gaps = Vec::new();
+        let mut cursor = Some(start);
+
+        // 起点在 `start` 之前的区间可能覆盖 `start`，需一并考虑
+        let first = self
+            .transitions
+            .range(..=start)
+            .next_back()
+            .map_or(start, |(&s, _)| s);
+        for (&range_start, &(range_end, _)) in self.transitions.range(first..=end) {
+            let Some(from) = cursor else {
+                break;
+            };
+            if range_end < from {
+                continue;
+            }
+            if range_start > from {
+                gaps.push((from, fsa::prev_char(range_start).unwrap()));
+            }
+            cursor = fsa::next_char(range_end);
+        }
+        if let Some(from) = cursor.filter(|&c| c <= end) {
+            gaps.push((from, end));
+        }
+
+        gaps
     }
 }
 
-type StateSet = fsa::StateSet<State>;
-type StateSetGroup = HashSet<StateSet>;
+/// 可细化的划分：同一块的元素在 `elements` 中连续存放，块内被标记的元素排在前部
+struct Partition {
+    elements: Vec<usize>,
+    /// 元素在 `elements` 中的下标
+    location: Vec<usize>,
+    block_of: Vec<usize>,
+    blocks: Vec<Block>,
+    /// 含有被标记元素的块
+    touched: Vec<usize>,
+}
 
-impl StateSet {
-    /// 按照 `终态` / `非终态` 进行分组
-    fn divide_by_acceptable(&self) -> StateSetGroup {
-        let mut unacceptable = Self::new();
-        let mut acceptable = Self::new();
+/// 块在 `elements` 中的范围，`[start, marked)` 为被标记的元素
+struct Block {
+    start: usize,
+    marked: usize,
+    end: usize,
+}
 
-        for state in self.states() {
-            if state.borrow().acceptable {
-                acceptable.insert(state);
-            } else {
-                unacceptable.insert(state);
+impl Partition {
+    fn new(len: usize, blocks: impl IntoIterator<Item = Vec<usize>>) -> Self {
+        let mut partition = Self {
+            elements: Vec::with_capacity(len),
+            location: vec![0; len],
+            block_of: vec![0; len],
+            blocks: Vec::new(),
+            touched: Vec::new(),
+        };
+        for block in blocks {
+            let start = partition.elements.len();
+            for element in block {
+                partition.location[element] = partition.elements.len();
+                partition.block_of[element] = partition.blocks.len();
+                partition.elements.push(element);
             }
+            partition.blocks.push(Block {
+                start,
+                marked: start,
+                end: partition.elements.len(),
+            });
+        }
+        partition
+    }
+
+    /// 块数目
+    #[inline]
+    fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    #[inline]
+    fn size(&self, block: usize) -> usize {
+        self.blocks[block].end - self.blocks[block].start
+    }
+
+    #[inline]
+    fn elements(&self, block: usize) -> &[usize] {
+        let block = &self.blocks[block];
+        &self.elements[block.start..block.end]
+    }
+
+    /// 标记元素，将其交换至所在块的被标记部分
+    fn mark(&mut self, element: usize) {
+        let block_id = self.block_of[element];
+        let block = &mut self.blocks[block_id];
+        let location = self.location[element];
+        if location < block.marked {
+            return;
+        }
+        if block.marked == block.start {
+            self.touched.push(block_id);
         }
 
-        HashSet::from([unacceptable, acceptable])
+        let other = self.elements[block.marked];
+        self.elements.swap(location, block.marked);
+        self.location[other] = location;
+        self.location[element] = block.marked;
+        block.marked += 1;
+    }
+
+    /// 将部分元素被标记的块一分为二，被标记的元素成为新块；返回所有 (原块, 新块)
+    fn split(&mut self) -> Vec<(usize, usize)> {
+        let mut splits = Vec::new();
+        for block_id in std::mem::take(&mut self.touched) {
+            let block = &mut self.blocks[block_id];
+            if block.marked == block.end {
+                block.marked = block.start;
+                continue;
+            }
+
+            let new = Block {
+                start: block.start,
+                marked: block.start,
+                end: block.marked,
+            };
+            block.start = block.marked;
+
+            let new_id = self.blocks.len();
+            for &element in &self.elements[new.start..new.end] {
+                self.block_of[element] = new_id;
+            }
+            self.blocks.push(new);
+            splits.push((block_id, new_id));
+        }
+        splits
     }
+}
 
-    /// 拆分，将状态集根据目前的状态集组拆分成独立的 N 组
-    fn divide(&self, groups: &StateSetGroup) -> StateSetGroup {
-        let symbols = self.alphabet();
-        let mut sets = HashMap::new();
+#[cfg(test)]
+mod tests {
+    use super::{Sampler, DFA};
+    use crate::fsa::biguint::BigUint;
+    use crate::fsa::nfa::NFA;
+    use crate::fsa::{Symbol, XorShift};
+    use crate::regex::{parse, Regex};
+    use std::collections::{BTreeSet, HashMap};
 
-        for state in self.states() {
-            let sets_of_symbols = symbols
+    const PATTERNS: &[&str] = &[
+        "",
+        "a",
+        "a*",
+        "(a|b)*abb",
+        "(a|b)*a(a|b)(a|b)",
+        "(ab|a)(bc|c)",
+        "a+b+|b+a+",
+        "(a*b*)*",
+        "c(a|b)?c?",
+        "[a-c]+[b-d]*",
+        "[^a]b|.c",
+        "(ca|cab|cabd)d?",
+        "\\d+(\\.\\d+)?",
+        "[\u{80}-\u{10FFFF}]+é",
+        "((a|b)(a|b))*",
+    ];
+
+    /// 字母表 {a, b} 上的正则表达式，最小 DFA 的状态数目均不超过 `TWO_LETTER_STATES`
+    const TWO_LETTER_PATTERNS: &[&str] = &[
+        "",
+        "a",
+        "a*",
+        "(a|b)*",
+        "(a*b*)*",
+        "a*(b|a)*",
+        "(a|b)*abb",
+        "a+b+|b+a+",
+        "((a|b)(a|b))*",
+        "a*|b*",
+        "(ab|a)(b|ab)",
+        "(a|b)?(a|b)?b",
+        "(ab)*a?",
+        "(a|b)*(aa|bb)(a|b)*",
+        "ab|ba|aab|bba",
+    ];
+    const TWO_LETTER_STATES: usize = 6;
+
+    /// 两字母表上的最小 DFA，以及按短字典序排列的全部字符串中被接受与否
+    ///
+    /// 补全死状态后最多 `n = TWO_LETTER_STATES + 1` 个状态，长度不超过 `2n - 1`
+    /// 即足以判定：最短的不被接受的串长度小于 `n`，语言无限当且仅当接受某个长度在 `[n, 2n)` 中的串
+    fn two_letter_dfas() -> impl Iterator<Item = (&'static str, DFA, Vec<(String, bool)>)> {
+        let strings = all_strings(&['a', 'b'], 2 * TWO_LETTER_STATES + 1);
+        TWO_LETTER_PATTERNS.iter().map(move |&pattern| {
+            let dfa = parse(pattern).unwrap().as_nfa().as_dfa().minimize();
+            assert!(dfa.len() <= TWO_LETTER_STATES, "{pattern}");
+            let accepted = strings
                 .iter()
-                .copied()
-                .map(|symbol| {
-                    // 此刻状态经过符号变换后落入到的在传入状态集组的状态集
-                    state
-                        .borrow()
-                        .next_state(symbol)
-                        .and_then(|s| groups.iter().find(|set| set.contains(&s.borrow())))
+                .map(|string| {
+                    let accepted = accepts(&dfa, string.chars().map(Symbol::from));
+                    (string.clone(), accepted)
                 })
+                .collect();
+            (pattern, dfa, accepted)
+        })
+    }
+
+    /// 字母表上长度不超过 `max_len` 的所有字符串
+    fn all_strings(alphabet: &[char], max_len: usize) -> Vec<String> {
+        let mut strings = vec![String::new()];
+        let mut last = strings.clone();
+        for _ in 0..max_len {
+            last = last
+                .iter()
+                .flat_map(|s| alphabet.iter().map(move |&c| format!("{s}{c}")))
+                .collect();
+            strings.extend(last.iter().cloned());
+        }
+        strings
+    }
+
+    fn accepts(dfa: &DFA, symbols: impl IntoIterator<Item = Symbol>) -> bool {
+        dfa.end_of(symbols)
+            .is_some_and(|id| dfa.state(id).acceptable())
+    }
+
+    /// 随机生成的正则表达式
+    fn random_pattern(rng: &mut XorShift, depth: u32) -> String {
+        if depth == 0 {
+            return ["a", "b", "c", "[ab]", "[^a]"][rng.below(5) as usize].to_string();
+        }
+        let choice = rng.below(6);
+        let mut sub = || random_pattern(rng, depth - 1);
+        match choice {
+            0 | 5 => format!("{}{}", sub(), sub()),
+            1 => format!("({}|{})", sub(), sub()),
+            2 => format!("({})*", sub()),
+            3 => format!("({})+", sub()),
+            _ => format!("({})?", sub()),
+        }
+    }
+
+    /// 最小化前后接受同样的字符串，且再次最小化不再减少状态
+    fn check_minimize(pattern: &str, strings: &[String]) {
+        let dfa = parse(pattern).unwrap().as_nfa().as_dfa();
+        let minimal = dfa.minimize();
+        assert!(minimal.len() <= dfa.len(), "{pattern}");
+        assert_eq!(minimal.minimize().len(), minimal.len(), "{pattern}");
+        for string in strings {
+            let symbols = || string.chars().map(Symbol::from);
+            assert_eq!(
+                accepts(&minimal, symbols()),
+                accepts(&dfa, symbols()),
+                "{pattern} {string:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn minimize_keeps_language() {
+        let strings = all_strings(&['a', 'b', 'c', 'd', '1', '.', 'é'], 4);
+        for pattern in PATTERNS {
+            check_minimize(pattern, &strings);
+        }
+    }
+
+    #[test]
+    fn minimize_random_patterns() {
+        let strings = all_strings(&['a', 'b', 'c'], 6);
+        let mut rng = XorShift(0xDEAD_BEEF_1234);
+        for _ in 0..300 {
+            check_minimize(&random_pattern(&mut rng, 4), &strings);
+        }
+    }
+
+    #[test]
+    fn minimize_known_sizes() {
+        let len = |pattern| parse(pattern).unwrap().as_nfa().as_dfa().minimize().len();
+        assert_eq!(len("(a|b)*abb"), 4);
+        assert_eq!(len("(a|b)*a(a|b)(a|b)"), 8);
+        assert_eq!(len("((a|b)(a|b))*"), 2);
+        assert_eq!(len("a*|b*"), 3);
+    }
+
+    #[test]
+    fn minimize_agrees_with_brzozowski() {
+        let strings = all_strings(&['a', 'b', 'c'], 5);
+        let mut rng = XorShift(0x5EED_CAFE);
+        let random = (0..100).map(|_| random_pattern(&mut rng, 4));
+        for pattern in PATTERNS.iter().map(|p| p.to_string()).chain(random) {
+            let dfa = parse(&pattern).unwrap().as_nfa().as_dfa();
+            let brzozowski = dfa.minimize_brzozowski();
+            assert_eq!(brzozowski.len(), dfa.minimize().len(), "{pattern}");
+            for string in &strings {
+                let symbols = || string.chars().map(Symbol::from);
+                assert_eq!(
+                    accepts(&brzozowski, symbols()),
+                    accepts(&dfa, symbols()),
+                    "{pattern} {string:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reverse_keeps_every_rule() {
+        let rules = ["ab".as_nfa(), "c".as_nfa(), parse("d*e").unwrap().as_nfa()];
+        let nfa = NFA::from_rules(rules);
+        let dfa = nfa.as_dfa();
+        let marked = dfa.states().filter_map(|(_, state)| state.rule());
+        assert_eq!(marked.collect::<BTreeSet<_>>(), BTreeSet::from([0, 1, 2]));
+
+        for reversed in [dfa.reverse().as_dfa(), nfa.reverse().as_dfa()] {
+            let accepts = |string: &str| accepts(&reversed, string.chars().map(Symbol::from));
+            for string in ["ba", "c", "e", "edd"] {
+                assert!(accepts(string), "{string:?}");
+            }
+            for string in ["", "ab", "de", "bac", "cc"] {
+                assert!(!accepts(string), "{string:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn equivalent_returns_shortest_counterexample() {
+        let dfa = |pattern| parse(pattern).unwrap().as_nfa().as_dfa();
+        assert_eq!(dfa("(a|b)*").equivalent(&dfa("(a*b*)*")), Ok(()));
+        assert_eq!(dfa("(a|b)*").equivalent(&dfa("(a|b)*").minimize()), Ok(()));
+        assert_eq!(
+            dfa("(a|b)*").equivalent(&dfa("a*b*")),
+            Err("ba".to_string())
+        );
+        assert_eq!(dfa("a+").equivalent(&dfa("a*")), Err(String::new()));
+        assert_eq!(
+            dfa("[a-c]x|by").equivalent(&dfa("[ab]x|by")),
+            Err("cx".to_string())
+        );
+    }
+
+    #[test]
+    fn minimize_in_byte_mode() {
+        let strings = all_strings(&['a', 'b', 'c', 'é'], 3);
+        for pattern in PATTERNS {
+            let dfa = parse(pattern).unwrap().as_nfa().into_bytes().as_dfa();
+            let minimal = dfa.minimize();
+            assert_eq!(minimal.minimize().len(), minimal.len(), "{pattern}");
+            for string in &strings {
+                let symbols = || string.bytes().map(Symbol::from);
+                assert_eq!(
+                    accepts(&minimal, symbols()),
+                    accepts(&dfa, symbols()),
+                    "{pattern} {string:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn complete_adds_absorbing_dead_state() {
+        let alphabet = [Symbol::Range('a', 'b')];
+        let dfa = parse("ab").unwrap().as_nfa().as_dfa().minimize();
+        let complete = dfa.complete(alphabet);
+        assert_eq!(complete.len(), dfa.len() + 1);
+        for (_, state) in complete.states() {
+            assert!(
+                state.next_state('a'.into()).is_some() && state.next_state('b'.into()).is_some()
+            );
+        }
+
+        // 死状态不可接受，字母表中的每个字符都回到自身，字母表之外仍没有转移
+        let dead = complete.end_of("b".chars().map(Symbol::from)).unwrap();
+        assert!(!complete.state(dead).acceptable());
+        assert_eq!(complete.state(dead).next_state('a'.into()), Some(dead));
+        assert_eq!(complete.state(dead).next_state('b'.into()), Some(dead));
+        assert_eq!(complete.state(dead).next_state('c'.into()), None);
+        let string = "ba".repeat(10);
+        assert_eq!(
+            complete.end_of(string.chars().map(Symbol::from)),
+            Some(dead)
+        );
+
+        // 已经完整时不再新增状态
+        assert_eq!(complete.complete(alphabet).len(), complete.len());
+    }
+
+    #[test]
+    fn complete_keeps_language_over_two_letters() {
+        for (pattern, dfa, strings) in two_letter_dfas() {
+            let complete = dfa.complete([Symbol::Range('a', 'b')]);
+            for (string, accepted) in &strings {
+                let symbols = string.chars().map(Symbol::from);
+                assert_eq!(
+                    accepts(&complete, symbols),
+                    *accepted,
+                    "{pattern} {string:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn complement_over_two_letters() {
+        for (pattern, dfa, strings) in two_letter_dfas() {
+            let complement = dfa.complement();
+            let double = complement.complement();
+            for (string, accepted) in &strings {
+                let symbols = || string.chars().map(Symbol::from);
+                assert_eq!(
+                    accepts(&complement, symbols()),
+                    !accepted,
+                    "{pattern} {string:?}"
+                );
+                assert_eq!(
+                    accepts(&double, symbols()),
+                    *accepted,
+                    "{pattern} {string:?}"
+                );
+            }
+        }
+
+        // 两次取补后语言不变
+        let strings = all_strings(&['a', 'b', 'c', 'd', '1', '.', 'é'], 4);
+        for pattern in PATTERNS {
+            let dfa = parse(pattern).unwrap().as_nfa().as_dfa();
+            let double = dfa.complement().complement();
+            for string in &strings {
+                let symbols = || string.chars().map(Symbol::from);
+                assert_eq!(
+                    accepts(&double, symbols()),
+                    accepts(&dfa, symbols()),
+                    "{pattern} {string:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn product_over_two_letters() {
+        let dfas = two_letter_dfas().collect::<Vec<_>>();
+        for (a, dfa_a, strings_a) in &dfas {
+            for (b, dfa_b, strings_b) in &dfas {
+                let products = [
+                    (
+                        dfa_a.union(dfa_b),
+                        (|x, y| x || y) as fn(bool, bool) -> bool,
+                    ),
+                    (dfa_a.intersect(dfa_b), |x, y| x && y),
+                    (dfa_a.difference(dfa_b), |x, y| x && !y),
+                ];
+                // 逐一比较的开销与串的数目成正比，只取长度不超过 8 的串
+                let strings = strings_a.iter().zip(strings_b);
+                for ((string, x), (_, y)) in strings.take_while(|((s, _), _)| s.len() <= 8) {
+                    for (product, op) in &products {
+                        let symbols = string.chars().map(Symbol::from);
+                        assert_eq!(accepts(product, symbols), op(*x, *y), "{a} {b} {string:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn subset_overlap_and_emptiness() {
+        let dfa = |pattern| parse(pattern).unwrap().as_nfa().as_dfa();
+        assert_eq!(dfa("a+").is_subset_of(&dfa("a*")), Ok(()));
+        assert_eq!(dfa("a*").is_subset_of(&dfa("a+")), Err(String::new()));
+        assert_eq!(
+            dfa("ab|ba").is_subset_of(&dfa("a.|bb")),
+            Err("ba".to_string())
+        );
+
+        assert_eq!(dfa("ab|c").overlaps(&dfa("a.")), Some("ab".to_string()));
+        assert_eq!(dfa("a*b").overlaps(&dfa("b|aab|c")), Some("b".to_string()));
+        assert_eq!(dfa("a+").overlaps(&dfa("b+")), None);
+        assert_eq!(dfa("(ab)*").overlaps(&dfa("a(ba)*")), None);
+
+        let a = dfa("a");
+        assert!(a.difference(&a).is_empty());
+        assert!(dfa("a+").intersect(&dfa("b+")).is_empty());
+        for pattern in ["", "a", "a*", "(a|b)*abb"] {
+            assert!(!dfa(pattern).is_empty(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn is_universal_over_two_letters() {
+        for (pattern, dfa, strings) in two_letter_dfas() {
+            let rejected = strings.into_iter().find(|(_, accepted)| !accepted);
+            assert_eq!(
+                dfa.is_universal([Symbol::Range('a', 'b')]),
+                rejected.map_or(Ok(()), |(string, _)| Err(string)),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn strings_over_two_letters() {
+        for (pattern, dfa, strings) in two_letter_dfas() {
+            let max_len = strings.last().unwrap().0.len();
+            let expected = strings
+                .into_iter()
+                .filter_map(|(string, accepted)| accepted.then_some(string))
+                .collect::<Vec<_>>();
+            let produced = dfa
+                .strings()
+                .take_while(|string| string.len() <= max_len)
+                .collect::<Vec<_>>();
+            assert_eq!(produced, expected, "{pattern}");
+            // 语言有限时全部产生后即结束
+            if dfa.is_finite() {
+                assert_eq!(dfa.strings().count(), expected.len(), "{pattern}");
+            }
+        }
+    }
+
+    #[test]
+    fn strings_stop_at_max_len() {
+        let dfa = parse("a*").unwrap().as_nfa().as_dfa();
+        let mut strings = dfa.strings().max_len(2);
+        assert_eq!(strings.next().as_deref(), Some(""));
+        assert_eq!(strings.next().as_deref(), Some("a"));
+        assert_eq!(strings.next().as_deref(), Some("aa"));
+        assert_eq!(strings.next(), None);
+        assert_eq!(strings.next(), None);
+
+        let dfa = parse("(a|b)*c").unwrap().as_nfa().as_dfa();
+        let strings = dfa.strings().max_len(2).collect::<Vec<_>>();
+        assert_eq!(strings, ["c", "ac", "bc"]);
+        assert_eq!(dfa.strings().max_len(0).count(), 0);
+    }
+
+    #[test]
+    fn counts_and_finiteness_over_two_letters() {
+        let n = TWO_LETTER_STATES + 1;
+        for (pattern, dfa, strings) in two_letter_dfas() {
+            let lengths = strings
+                .iter()
+                .filter(|(_, accepted)| *accepted)
+                .map(|(string, _)| string.len())
                 .collect::<Vec<_>>();
-            sets.entry(sets_of_symbols)
-                .or_insert(LinkedList::new())
-                .push_back(state.clone());
+            let mut total = 0;
+            for len in 0..2 * n {
+                let count = lengths.iter().filter(|&&l| l == len).count();
+                total += count;
+                assert_eq!(dfa.count_of_length(len), count.into(), "{pattern} {len}");
+                assert_eq!(dfa.count_up_to(len), total.into(), "{pattern} {len}");
+            }
+
+            let finite = !lengths.iter().any(|&len| (n..2 * n).contains(&len));
+            assert_eq!(dfa.is_finite(), finite, "{pattern}");
+            let max_length = lengths.iter().copied().max().filter(|_| finite);
+            assert_eq!(dfa.max_length(), max_length, "{pattern}");
+        }
+
+        // 空语言有限，但没有最长的字符串
+        let a = parse("a").unwrap().as_nfa().as_dfa();
+        let empty = a.difference(&a);
+        assert!(empty.is_finite());
+        assert_eq!(empty.max_length(), None);
+        assert_eq!(empty.count_up_to(3), 0_usize.into());
+    }
+
+    #[test]
+    fn counts_stop_once_no_state_is_reached() {
+        // 语言有限时，读入的字符超过最长路径后不再有可达的状态，无须计算到 `n`
+        let dfa = parse("ab|[c-e]").unwrap().as_nfa().as_dfa();
+        assert_eq!(dfa.count_of_length(usize::MAX), 0_usize.into());
+        assert_eq!(dfa.count_up_to(usize::MAX), 4_usize.into());
+
+        let dfa = parse("[a-z]*").unwrap().as_nfa().as_dfa();
+        let (mut expected, mut total) = (BigUint::from(1u64), BigUint::zero());
+        for n in 0..=40 {
+            total += &expected;
+            assert_eq!(dfa.count_of_length(n), expected, "{n}");
+            assert_eq!(dfa.count_up_to(n), total, "{n}");
+            expected = &expected * 26;
         }
+    }
+
+    #[test]
+    fn sampler_stays_in_range() {
+        let mut rng = XorShift(0x0123_4567_89AB_CDEF);
+        let dfa = |pattern| parse(pattern).unwrap().as_nfa().as_dfa();
 
-        StateSetGroup::from_iter(sets.into_values().map(StateSet::from_states))
+        // 语言有限时无界的范围截断为最长字符串的长度
+        let finite = dfa("a|ab|abb|ba");
+        let sampler = Sampler::new(&finite, 0..=usize::MAX).unwrap();
+        assert_eq!(sampler.count(), &4_usize.into());
+        let sampler = Sampler::new(&finite, 4..=usize::MAX).unwrap();
+        assert!(sampler.count().is_zero());
+        assert_eq!(sampler.sample(&mut rng), None);
+
+        for (pattern, lengths, counted) in [
+            ("a|ab|abb|ba", 2..=usize::MAX, 2..=3),
+            ("(a|b)*abb", 3..=9, 3..=9),
+        ] {
+            let dfa = dfa(pattern);
+            let sampler = Sampler::new(&dfa, lengths.clone()).unwrap();
+            let counts = counted.map(|n| dfa.count_of_length(n)).collect::<Vec<_>>();
+            assert_eq!(sampler.count(), &counts.iter().sum(), "{pattern}");
+            for _ in 0..200 {
+                let string = sampler.sample(&mut rng).unwrap();
+                assert!(lengths.contains(&string.chars().count()), "{string}");
+                assert!(accepts(&dfa, string.chars().map(Symbol::from)), "{string}");
+            }
+        }
+    }
+
+    #[test]
+    fn sampler_rejects_long_bound_of_infinite_language() {
+        let dfa = parse("a*").unwrap().as_nfa().as_dfa();
+        assert!(Sampler::new(&dfa, 0..=usize::MAX).is_none());
+        assert!(Sampler::new(&dfa, 0..=Sampler::MAX_LENGTH + 1).is_none());
+        let sampler = Sampler::new(&dfa, 0..=Sampler::MAX_LENGTH).unwrap();
+        assert_eq!(sampler.count(), &(Sampler::MAX_LENGTH + 1).into());
+    }
+
+    #[test]
+    fn sampler_is_uniform_over_strings() {
+        // 按长度均匀时 "a" 与 "ab" 各占四分之一、"abc" 与 "abd" 各占八分之一
+        let dfa = parse("a|ab|abc|abd").unwrap().as_nfa().as_dfa();
+        let sampler = Sampler::new(&dfa, 0..=usize::MAX).unwrap();
+        let mut rng = XorShift(0x0BAD_5EED_F00D);
+        let mut counts = HashMap::new();
+        for _ in 0..4000 {
+            *counts.entry(sampler.sample(&mut rng).unwrap()).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 4);
+        for (string, count) in counts {
+            assert!((900..=1100).contains(&count), "{string}: {count}");
+        }
     }
 }
@@ -0,0 +1,233 @@
+use crate::fsa::dense::DenseDFA;
+use crate::fsa::nfa::{Threads, NFA};
+use crate::fsa::Symbol;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::Arc;
+
+/// 惰性 DFA：子集构造按需进行，仅当输入到达某个状态集时才计算其 move 与 ε-closure
+///
+/// 非锚定时自输入的每个位置起始匹配，按最左最长搜索的语义到达终态，见 `NFA::as_search_dfa_with_limit`
+///
+/// 已计算的状态及转移缓存起来，缓存占用超出容量时整体清空后继续，因此内存占用有界，
+/// 而通常的输入仍只需访问少量状态，可获得接近 DFA 的速度
+#[derive(Debug)]
+pub struct LazyDFA {
+    /// 字节模式的 NFA，同一自动机的各份缓存共享
+    nfa: Arc<NFA>,
+    /// 初始状态，含始态的 ε-closure
+    start: Arc<Threads>,
+    cache: Cache,
+}
+
+impl LazyDFA {
+    /// 默认的缓存容量（字节）
+    pub const DEFAULT_CACHE_CAPACITY: usize = 2 << 20;
+
+    /// 死状态，约定同 `DenseDFA::DEAD`
+    const DEAD: u32 = DenseDFA::DEAD;
+
+    /// 尚未计算的转移
+    const UNKNOWN: u32 = u32::MAX;
+
+    /// 由字符模式的 NFA（如 `Regex::as_nfa` 的结果）构建，`capacity` 为缓存容量（字节）
+    #[inline]
+    pub fn new(nfa: NFA, capacity: usize) -> Self {
+        Self::from_bytes(nfa.into_bytes(), true, capacity)
+    }
+
+    /// 由字节模式的 NFA 构建，`anchored` 为 `false` 时用于搜索
+    pub(crate) fn from_bytes(nfa: NFA, anchored: bool, capacity: usize) -> Self {
+        let start = Arc::new(nfa.threads_start(anchored));
+        Self::with_cache(Arc::new(nfa), start, capacity)
+    }
+
+    /// 共享同一 NFA、缓存为空的新实例，供多个线程各自使用
+    #[inline]
+    pub(crate) fn fresh(&self) -> Self {
+        Self::with_cache(self.nfa.clone(), self.start.clone(), self.cache.capacity)
+    }
+
+    /// 以空缓存构建
+    fn with_cache(nfa: Arc<NFA>, start: Arc<Threads>, capacity: usize) -> Self {
+        let mut lazy = Self {
+            nfa,
+            start,
+            cache: Cache {
+                states: Vec::new(),
+                ids: HashMap::new(),
+                memory: 0,
+                capacity,
+                clears: 0,
+            },
+        };
+        lazy.cache.clear();
+        lazy
+    }
+
+    /// 整个输入是否匹配
+    pub fn is_match(&mut self, input: impl AsRef<[u8]>) -> bool {
+        let mut state = self.start_state();
+        for &byte in input.as_ref() {
+            state = self.next_byte(state, byte);
+            if state == Self::DEAD {
+                return false;
+            }
+        }
+        self.cache.states[state as usize].acceptable
+    }
+
+    /// 依次读入字节，每当到达终态时以已读入的字节数回调 `on_accept`，落入死状态即停止
+    #[inline]
+    pub fn scan(&mut self, input: impl IntoIterator<Item = u8>, on_accept: impl FnMut(usize)) {
+        self.try_scan(input, on_accept, usize::MAX);
+    }
+
+    /// 同 `scan`，但本次扫描中缓存被清空超过 `max_clears` 次时放弃并返回 `false`
+    ///
+    /// 缓存反复清空说明输入需要的状态远超容量，此时逐字节确定化已不比直接模拟 NFA 更快
+    pub(crate) fn try_scan(
+        &mut self,
+        input: impl IntoIterator<Item = u8>,
+        mut on_accept: impl FnMut(usize),
+        max_clears: usize,
+    ) -> bool {
+        let clears = self.cache.clears;
+        let mut state = self.start_state();
+        if self.cache.states[state as usize].acceptable {
+            on_accept(0);
+        }
+        for (i, byte) in input.into_iter().enumerate() {
+            state = self.next_byte(state, byte);
+            if self.cache.clears - clears > max_clears {
+                return false;
+            }
+            if state == Self::DEAD {
+                break;
+            }
+            if self.cache.states[state as usize].acceptable {
+                on_accept(i + 1);
+            }
+        }
+        true
+    }
+
+    /// 缓存的状态数目（含死状态）
+    #[inline]
+    pub fn cache_len(&self) -> usize {
+        self.cache.states.len()
+    }
+
+    /// 缓存估计占用的内存（字节）
+    #[inline]
+    pub fn memory_usage(&self) -> usize {
+        self.cache.memory
+    }
+
+    /// 缓存被清空的次数
+    #[inline]
+    pub fn cache_clears(&self) -> usize {
+        self.cache.clears
+    }
+
+    #[inline]
+    fn start_state(&mut self) -> u32 {
+        let start = self.start.clone();
+        if !self.cache.ids.contains_key(&start) && self.cache.is_full(&start) {
+            self.cache.clear();
+        }
+        self.cache.insert(start)
+    }
+
+    /// 根据字节获取下一个状态，转移未知时计算并缓存；缓存已满时先清空，仅保留当前状态
+    fn next_byte(&mut self, mut state: u32, byte: u8) -> u32 {
+        let next = self.cache.states[state as usize].next[byte as usize];
+        if next != Self::UNKNOWN {
+            return next;
+        }
+
+        let current = self.cache.states[state as usize].threads.clone();
+        let threads = Arc::new(self.nfa.threads_next(&current, Symbol::Byte(byte)));
+        if !self.cache.ids.contains_key(&threads) && self.cache.is_full(&threads) {
+            self.cache.clear();
+            state = self.cache.insert(current);
+        }
+
+        let next = self.cache.insert(threads);
+        self.cache.states[state as usize].next[byte as usize] = next;
+        next
+    }
+}
+
+/// 已计算的状态，下标即状态编号
+#[derive(Debug)]
+struct Cache {
+    states: Vec<CachedState>,
+    ids: HashMap<Arc<Threads>, u32>,
+    /// 估计占用的内存（字节）
+    memory: usize,
+    capacity: usize,
+    clears: usize,
+}
+
+impl Cache {
+    /// 加入 `threads` 后是否超出容量，仅含死状态时总可加入
+    #[inline]
+    fn is_full(&self, threads: &Threads) -> bool {
+        self.states.len() > 1 && self.memory + Self::cost(threads) > self.capacity
+    }
+
+    /// 清空缓存，仅保留死状态
+    fn clear(&mut self) {
+        if !self.states.is_empty() {
+            self.clears += 1;
+        }
+        self.states.clear();
+        self.ids.clear();
+        self.memory = 0;
+
+        // 死状态对应空集，所有转移都指向自身
+        let dead = Arc::new(Threads::default());
+        self.memory += Self::cost(&dead);
+        self.states.push(CachedState {
+            threads: dead.clone(),
+            acceptable: false,
+            next: Box::new([LazyDFA::DEAD; 256]),
+        });
+        self.ids.insert(dead, LazyDFA::DEAD);
+    }
+
+    /// 加入状态并返回其状态编号，已存在时直接返回
+    fn insert(&mut self, threads: Arc<Threads>) -> u32 {
+        if let Some(&id) = self.ids.get(&threads) {
+            return id;
+        }
+
+        let id = self.states.len() as u32;
+        self.memory += Self::cost(&threads);
+        self.states.push(CachedState {
+            threads: threads.clone(),
+            acceptable: threads.accepted(),
+            next: Box::new([LazyDFA::UNKNOWN; 256]),
+        });
+        self.ids.insert(threads, id);
+        id
+    }
+
+    /// 状态占用内存的估计值：转移表、状态集及索引
+    #[inline]
+    fn cost(threads: &Threads) -> usize {
+        size_of::<CachedState>()
+            + size_of::<[u32; 256]>()
+            + threads.len() * size_of::<usize>()
+            + size_of::<(Arc<Threads>, u32)>()
+    }
+}
+
+#[derive(Debug)]
+struct CachedState {
+    threads: Arc<Threads>,
+    acceptable: bool,
+    /// 各字节的转移，`LazyDFA::UNKNOWN` 表示尚未计算
+    next: Box<[u32; 256]>,
+}
@@ -1,23 +1,69 @@
-use std::cell::RefCell;
-use std::collections::{BTreeSet, HashMap, HashSet};
-use std::fmt::{Debug, Formatter};
-use std::hash::{Hash, Hasher};
-use std::rc::Rc;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
+pub mod biguint;
+pub mod dense;
 pub mod dfa;
+pub mod lazy;
 pub mod nfa;
+mod utf8;
 
 /// 输入符号
+///
+/// 转移以字符区间 `Range` 为标记，单个字符 `Char(c)` 等价于 `Range(c, c)`
+///
+/// 字节模式的自动机（见 `NFA::into_bytes`）中，字节 `b` 视作码点为 `b` 的字符，
+/// 即 `Byte(b)` 等价于 `Char(b as char)`
+///
+/// `Tag(t)` 为带标记的 ε 转移，用于记录捕获分组的位置：分组 `g` 的起点、终点
+/// 分别标记为 `Tag(2g)`、`Tag(2g + 1)`，分组 0 即整个匹配，不出现在自动机中
 #[derive(Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Symbol {
     Epsilon,
     Char(char),
+    Byte(u8),
+    Range(char, char),
+    Tag(usize),
 }
 
 impl Symbol {
+    /// 是否不消耗输入，`Tag` 同样视作 ε
     #[inline]
     pub fn is_epsilon(&self) -> bool {
-        self == &Self::Epsilon
+        matches!(self, Self::Epsilon | Self::Tag(_))
+    }
+
+    /// 符号对应的字符区间，ε 没有区间
+    #[inline]
+    pub fn range(&self) -> Option<(char, char)> {
+        match *self {
+            Self::Epsilon | Self::Tag(_) => None,
+            Self::Char(c) => Some((c, c)),
+            Self::Byte(b) => Some((b.into(), b.into())),
+            Self::Range(start, end) => Some((start, end)),
+        }
+    }
+
+    /// 统一为区间形式，`Char(c)` 转为 `Range(c, c)`
+    #[inline]
+    fn normalize(self) -> Self {
+        match self.range() {
+            Some((start, end)) => Self::Range(start, end),
+            None => self,
+        }
+    }
+
+    /// 是否完整覆盖另一个符号
+    #[inline]
+    fn covers(&self, other: Symbol) -> bool {
+        match (self.range(), other.range()) {
+            (Some((start, end)), Some((other_start, other_end))) => {
+                start <= other_start && other_end <= end
+            }
+            _ => false,
+        }
     }
 }
 
@@ -28,28 +74,88 @@ impl From<char> for Symbol {
     }
 }
 
+impl From<u8> for Symbol {
+    #[inline]
+    fn from(byte: u8) -> Self {
+        Symbol::Byte(byte)
+    }
+}
+
 impl Debug for Symbol {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Epsilon => write!(f, "ε"),
             Self::Char(c) => write!(f, "{c}"),
+            Self::Byte(b) => write!(f, "{b:#04X}"),
+            Self::Tag(t) => write!(f, "#{t}"),
+            Self::Range(start, end) if start == end => write!(f, "{start}"),
+            Self::Range(start, end) => write!(f, "{start}-{end}"),
+        }
+    }
+}
+
+/// 下一个 Unicode 标量值（跳过代理区）
+pub(crate) fn next_char(char: char) -> Option<char> {
+    match char {
+        '\u{D7FF}' => Some('\u{E000}'),
+        char::MAX => None,
+        _ => char::from_u32(char as u32 + 1),
+    }
+}
+
+/// 上一个 Unicode 标量值（跳过代理区）
+pub(crate) fn prev_char(char: char) -> Option<char> {
+    match char {
+        '\u{E000}' => Some('\u{D7FF}'),
+        '\0' => None,
+        _ => char::from_u32(char as u32 - 1),
+    }
+}
+
+/// 将可能重叠的符号拆分为互不相交的基本区间，每个输入符号都恰为若干基本区间之并
+fn split_symbols(symbols: impl IntoIterator<Item = Symbol>) -> Vec<Symbol> {
+    // 以码点为坐标，区间起点处覆盖数加一，终点之后减一
+    let mut events = BTreeMap::<u32, isize>::new();
+    for (start, end) in symbols.into_iter().filter_map(|s| s.range()) {
+        *events.entry(start as u32).or_default() += 1;
+        *events.entry(end as u32 + 1).or_default() -= 1;
+    }
+
+    let mut segments = Vec::new();
+    let mut covered = 0;
+    let mut events = events.into_iter().peekable();
+    while let Some((point, delta)) = events.next() {
+        covered += delta;
+        let Some(&(next, _)) = events.peek() else {
+            break;
+        };
+        if covered == 0 {
+            continue;
+        }
+        // 区间端点可能落入代理区，需收缩至合法的 Unicode 标量值
+        let start = (point..next).find_map(char::from_u32);
+        let end = (point..next).rev().find_map(char::from_u32);
+        if let (Some(start), Some(end)) = (start, end) {
+            segments.push(Symbol::Range(start, end));
         }
     }
+    segments
 }
 
-/// 状态 ID，作为每个状态的唯一表示，参与集合运算
+/// 状态 ID，即状态在所属自动机中的下标，由自动机分配，从 0 开始连续编号
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
-struct StateID(usize);
+pub struct StateID(usize);
 
 impl StateID {
-    fn obtain() -> Self {
-        static mut ID_RAW_POOL: usize = 0;
+    #[inline]
+    pub fn index(self) -> usize {
+        self.0
+    }
 
-        unsafe {
-            let raw = ID_RAW_POOL;
-            ID_RAW_POOL += 1;
-            Self(raw)
-        }
+    /// 平移下标，用于将一个自动机的状态并入另一个自动机
+    #[inline]
+    fn offset(self, offset: usize) -> Self {
+        Self(self.0 + offset)
     }
 }
 
@@ -60,115 +166,131 @@ impl Debug for StateID {
     }
 }
 
-/// 状态，其中包含标志其唯一的 ID
+/// 状态
 trait State {
-    /// 唯一的 ID
-    fn id(&self) -> StateID;
-
     /// 可接受的所有符号
     fn alphabet<'a>(&'a self) -> Box<dyn Iterator<Item = Symbol> + 'a>;
 }
 
-/// 状态节点，通过 Rc 和 RefCell 包裹
-type StateNode<T> = Rc<RefCell<T>>;
-
 /// 状态集，参与集合运算
-#[derive(Debug)]
-struct StateSet<T>(HashMap<StateID, StateNode<T>>);
+type StateSet = BTreeSet<StateID>;
+
+/// 规则（模式）编号集，编号越小优先级越高
+pub type RuleSet = BTreeSet<usize>;
 
-impl<T> StateSet<T>
+/// 一组状态可接受的所有符号（输入字母表除ε)，拆分为互不相交的基本区间
+fn alphabet<'a, T>(states: impl IntoIterator<Item = &'a T>) -> Vec<Symbol>
 where
-    T: State,
+    T: State + 'a,
 {
-    #[inline]
-    fn new() -> Self {
-        Self(HashMap::new())
-    }
-
-    #[inline]
-    fn from_single(state: StateNode<T>) -> Self {
-        let mut set = Self::new();
-        set.insert(state);
-        set
-    }
+    split_symbols(
+        states
+            .into_iter()
+            .flat_map(|s| s.alphabet())
+            .filter(|s| !s.is_epsilon()),
+    )
+}
 
-    #[inline]
-    fn from_states(states: impl IntoIterator<Item = StateNode<T>>) -> Self {
-        let mut set = Self::new();
-        set.extend(states);
-        set
-    }
+/// 自动机构建错误，用于拒绝会导致状态爆炸的正则表达式
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CompileError {
+    /// 确定化时 DFA 状态数目超出上限，`built` 为放弃构造时已构建的状态数目，不超过上限
+    TooManyStates { built: usize, limit: usize },
+    /// 稠密 DFA 转移表占用的内存（字节）超出上限
+    TooMuchMemory { used: usize, limit: usize },
+}
 
+impl CompileError {
+    /// 已构建 `built` 个状态时，检查能否再加入一个状态而不超出上限 `limit`
     #[inline]
-    fn insert(&mut self, state: StateNode<T>) {
-        let id = state.borrow().id();
-        self.0.insert(id, state);
+    pub(crate) fn check_states(built: usize, limit: usize) -> Result<(), Self> {
+        match built < limit {
+            true => Ok(()),
+            false => Err(Self::TooManyStates { built, limit }),
+        }
     }
+}
 
-    #[inline]
-    fn extend(&mut self, states: impl IntoIterator<Item = StateNode<T>>) {
-        self.0.extend(states.into_iter().map(|s| {
-            let id = s.borrow().id();
-            (id, s)
-        }))
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyStates { built, limit } => {
+                write!(
+                    f,
+                    "DFA 状态数目超出上限：已构建 {built} 个，上限 {limit} 个"
+                )
+            }
+            Self::TooMuchMemory { used, limit } => {
+                write!(
+                    f,
+                    "DFA 占用内存超出上限：需要 {used} 字节，上限 {limit} 字节"
+                )
+            }
+        }
     }
+}
 
-    #[inline]
-    fn is_empty(&self) -> bool {
-        self.0.is_empty()
-    }
+impl Error for CompileError {}
 
-    #[inline]
-    fn contains(&self, state: &T) -> bool {
-        self.0.contains_key(&state.id())
-    }
+/// 缓存池：每次使用时取出一份缓存独占使用，用毕放回，多个线程可同时使用各自的缓存
+///
+/// 池中缓存的数目不超过同时使用的线程数
+#[derive(Debug)]
+pub(crate) struct Pool<T> {
+    caches: Mutex<Vec<T>>,
+}
 
+impl<T> Pool<T> {
     #[inline]
-    fn states(&self) -> impl Iterator<Item = StateNode<T>> + '_ {
-        self.0.values().cloned()
+    pub(crate) fn new() -> Self {
+        Self {
+            caches: Mutex::new(Vec::new()),
+        }
     }
 
-    /// 可接受的所有符号（输入字母表除ε)
-    fn alphabet(&self) -> HashSet<Symbol> {
-        HashSet::from_iter(self.0.values().flat_map(|s| {
-            s.borrow()
-                .alphabet()
-                .filter(|s| !s.is_epsilon())
-                .collect::<Vec<_>>()
-        }))
+    /// 取出一份缓存（池为空时以 `create` 新建）执行 `f`，完成后放回；仅存取缓存时持有锁
+    pub(crate) fn with<R>(&self, create: impl FnOnce() -> T, f: impl FnOnce(&mut T) -> R) -> R {
+        let cache = self.lock().pop();
+        let mut cache = cache.unwrap_or_else(create);
+        let result = f(&mut cache);
+        self.lock().push(cache);
+        result
     }
 
-    /// 为判等、哈希运算提供支持
+    /// 其他线程在持有锁时崩溃不影响缓存本身的一致性，忽略中毒
     #[inline]
-    fn key(&self) -> BTreeSet<&StateID> {
-        BTreeSet::from_iter(self.0.keys())
+    fn lock(&self) -> MutexGuard<'_, Vec<T>> {
+        self.caches.lock().unwrap_or_else(PoisonError::into_inner)
     }
 }
 
-impl<T> Clone for StateSet<T> {
+/// 测试用的 xorshift 伪随机数生成器，种子固定时结果可复现，种子不可为零
+#[cfg(test)]
+pub(crate) struct XorShift(pub(crate) u64);
+
+#[cfg(test)]
+impl XorShift {
+    /// `[0, n)` 中的随机数
     #[inline]
-    fn clone(&self) -> Self {
-        Self(self.0.clone())
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        dfa::Rng::next_u64(self) % n
     }
-}
 
-impl<T> Hash for StateSet<T>
-where
-    T: State,
-{
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.key().hash(state)
+    /// 字符取自 `chars` 的随机串
+    pub(crate) fn string(&mut self, chars: &[char], len: usize) -> String {
+        (0..len)
+            .map(|_| chars[self.below(chars.len() as u64) as usize])
+            .collect()
     }
 }
 
-impl<T> Eq for StateSet<T> where T: State {}
-
-impl<T> PartialEq<Self> for StateSet<T>
-where
-    T: State,
-{
+#[cfg(test)]
+impl dfa::Rng for XorShift {
     #[inline]
-    fn eq(&self, other: &Self) -> bool {
-        self.key().eq(&other.key())
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
     }
 }
@@ -1,84 +1,456 @@
 use crate::fsa::{
-    self,
-    dfa::{self, DFA},
-    StateID, Symbol,
+    self, dfa::DFA, utf8::Utf8Sequences, CompileError, Pool, RuleSet, StateID, StateSet, Symbol,
 };
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet, LinkedList};
+use crate::{Captures, Match};
+use std::collections::{HashMap, LinkedList};
 use std::fmt::{Debug, Formatter};
+use std::ops::Range;
 use std::rc::Rc;
+use std::sync::Arc;
 
+/// NFA，所有状态存放于自身的状态表中，以 `StateID` 下标互相引用
+#[derive(Clone)]
 pub struct NFA {
-    start: StateNode,
-    end: StateNode,
+    states: Vec<State>,
+    start: StateID,
+    end: StateID,
+    /// 捕获分组 1、2… 的名称，分组以 `Symbol::Tag` 转移标记
+    groups: Vec<Option<String>>,
+    /// 是否为字节模式，见 `into_bytes`
+    bytes: bool,
 }
 
 impl NFA {
+    /// 构建仅含始态与终态（无任何转移）的 NFA
     #[inline]
-    pub fn new(start: StateNode, end: StateNode) -> Self {
-        Self { start, end }
+    pub fn new() -> Self {
+        let mut nfa = Self {
+            states: Vec::new(),
+            start: StateID(0),
+            end: StateID(0),
+            groups: Vec::new(),
+            bytes: false,
+        };
+        nfa.start = nfa.add_state();
+        nfa.end = nfa.add_state();
+        nfa
     }
 
     #[inline]
-    pub fn start(&self) -> StateNode {
-        self.start.clone()
+    pub fn start(&self) -> StateID {
+        self.start
     }
 
     #[inline]
-    pub fn end(&self) -> StateNode {
-        self.end.clone()
+    pub fn end(&self) -> StateID {
+        self.end
+    }
+
+    #[inline]
+    pub fn set_start(&mut self, start: StateID) {
+        self.start = start;
+    }
+
+    #[inline]
+    pub fn set_end(&mut self, end: StateID) {
+        self.end = end;
+    }
+
+    /// 是否为字节模式，见 `into_bytes`
+    #[inline]
+    pub fn is_bytes(&self) -> bool {
+        self.bytes
+    }
+
+    #[inline]
+    pub(crate) fn set_bytes(&mut self, bytes: bool) {
+        self.bytes = bytes;
+    }
+
+    #[inline]
+    pub fn state(&self, id: StateID) -> &State {
+        &self.states[id.0]
+    }
+
+    /// 状态数目
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// 捕获分组（不含分组 0）的名称，下标 `i` 对应分组 `i + 1`
+    #[inline]
+    pub fn groups(&self) -> &[Option<String>] {
+        &self.groups
+    }
+
+    /// 新增捕获分组，返回其编号（从 1 开始）
+    #[inline]
+    pub fn add_group(&mut self, name: Option<String>) -> usize {
+        self.groups.push(name);
+        self.groups.len()
+    }
+
+    /// 新增状态
+    #[inline]
+    pub fn add_state(&mut self) -> StateID {
+        self.states.push(State::default());
+        StateID(self.states.len() - 1)
+    }
+
+    /// ε 转移
+    #[inline]
+    pub fn e_transition(&mut self, from: StateID, to: StateID) -> &mut Self {
+        self.transition(from, Symbol::Epsilon, to)
+    }
+
+    /// 转移，字符转移统一以区间标记
+    #[inline]
+    pub fn transition(&mut self, from: StateID, symbol: Symbol, to: StateID) -> &mut Self {
+        self.states[from.0]
+            .transitions
+            .entry(symbol.normalize())
+            .or_default()
+            .push(to);
+        self
+    }
+
+    /// 将状态标记为接受规则 `rule` 的终态，用于多规则合并的 NFA（如词法分析器）
+    ///
+    /// 子集构造时，DFA 状态接受其 NFA 状态集所标记的全部规则；`end` 视作接受规则 0
+    #[inline]
+    pub fn accept(&mut self, state: StateID, rule: usize) -> &mut Self {
+        self.states[state.0].rule = Some(rule);
+        self
+    }
+
+    /// 合并多条规则：新始态经 ε 转移到达各规则的始态，第 `i` 条规则的终态接受规则 `i`
+    ///
+    /// 合并后的 `end` 不可达，仅以各规则标记的终态接受
+    pub fn from_rules(rules: impl IntoIterator<Item = NFA>) -> NFA {
+        let mut nfa = NFA::new();
+        let start = nfa.start;
+        for (rule, rule_nfa) in rules.into_iter().enumerate() {
+            let (rule_start, rule_end) = nfa.embed(rule_nfa);
+            nfa.e_transition(start, rule_start).accept(rule_end, rule);
+        }
+        nfa
+    }
+
+    /// 将另一个 NFA 的所有状态并入自身，返回其始态与终态在自身中的 ID
+    ///
+    /// 其捕获分组依次排在自身已有分组之后；`other` 为字节模式时自身同样成为字节模式
+    pub fn embed(&mut self, other: NFA) -> (StateID, StateID) {
+        let offset = self.states.len();
+        let tag_offset = self.groups.len() * 2;
+        // 仅当两侧都有分组时才需重新编号 `Tag`
+        let retag = tag_offset > 0 && !other.groups.is_empty();
+        self.states.reserve(other.states.len());
+        for mut state in other.states {
+            for target in state.transitions.values_mut().flatten() {
+                *target = target.offset(offset);
+            }
+            if retag {
+                let tags = state
+                    .transitions
+                    .keys()
+                    .filter_map(|&symbol| match symbol {
+                        Symbol::Tag(tag) => Some(tag),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                // 先全部取出再插入，避免新编号与尚未处理的旧编号冲突
+                let retagged = tags
+                    .into_iter()
+                    .map(|tag| (tag, state.transitions.remove(&Symbol::Tag(tag)).unwrap()))
+                    .collect::<Vec<_>>();
+                for (tag, targets) in retagged {
+                    state
+                        .transitions
+                        .insert(Symbol::Tag(tag + tag_offset), targets);
+                }
+            }
+            self.states.push(state);
+        }
+        self.groups.extend(other.groups);
+        self.bytes |= other.bytes;
+        (other.start.offset(offset), other.end.offset(offset))
     }
 
     /// 子集构造法 (Subset Construction) 转换成 DFA
+    #[inline]
     pub fn as_dfa(&self) -> DFA {
-        let start = Rc::new(StateSet::from_single(self.start.clone()).e_closure());
-        let dfa_start = dfa::State::new_node(start.contains(&self.end().borrow()));
+        self.as_dfa_with_limit(usize::MAX).unwrap()
+    }
+
+    /// 子集构造，DFA 状态数目将超过 `max_states` 时放弃构造并返回错误，已构建的状态数目不超过上限
+    pub fn as_dfa_with_limit(&self, max_states: usize) -> Result<DFA, CompileError> {
+        CompileError::check_states(0, max_states)?;
+        let start = Rc::new(self.e_closure(&StateSet::from([self.start])));
+        let mut dfa = DFA::new(false);
+        dfa.set_bytes(self.bytes);
+        dfa.set_rules(dfa.start(), self.rules_of(&start));
 
-        let mut dfa_states = HashMap::from([(start.clone(), dfa_start.clone())]);
+        let mut dfa_states = HashMap::from([(start.clone(), dfa.start())]);
         let mut queue = LinkedList::from([start]);
 
         while let Some(set) = queue.pop_front() {
-            for symbol in set.alphabet() {
-                let new_set = Rc::new(set.move_to(symbol).e_closure());
+            for symbol in self.alphabet(&set) {
+                let new_set = Rc::new(self.e_closure(&self.move_to(&set, symbol)));
                 if new_set.is_empty() {
                     continue;
                 }
 
-                if !dfa_states.contains_key(&new_set) {
-                    queue.push_back(new_set.clone());
+                let dfa_state = match dfa_states.get(&new_set) {
+                    Some(&id) => id,
+                    None => {
+                        CompileError::check_states(dfa.len(), max_states)?;
+                        let id = dfa.add_state(false);
+                        dfa.set_rules(id, self.rules_of(&new_set));
+                        dfa_states.insert(new_set.clone(), id);
+                        queue.push_back(new_set);
+                        id
+                    }
+                };
+                dfa.transition(dfa_states[&set], symbol, dfa_state);
+            }
+        }
+
+        Ok(dfa)
+    }
+
+    /// 最左最长搜索的 DFA：自输入的每个位置起始匹配，扫描至死状态时最后一次到达终态的位置
+    /// 即为最左最长匹配的终点
+    ///
+    /// 状态为按起点排列的线程集合（见 `Threads`），终态均接受规则 0。仅用于字节模式，
+    /// DFA 状态数目超过 `max_states` 时放弃构造并返回错误
+    pub(crate) fn as_search_dfa_with_limit(&self, max_states: usize) -> Result<DFA, CompileError> {
+        CompileError::check_states(0, max_states)?;
+        let start = Rc::new(self.threads_start(false));
+        let mut dfa = DFA::new(start.accepted);
+        dfa.set_bytes(self.bytes);
+
+        let mut dfa_states = HashMap::from([(start.clone(), dfa.start())]);
+        let mut queue = LinkedList::from([start]);
+
+        while let Some(threads) = queue.pop_front() {
+            for symbol in self.threads_alphabet(&threads) {
+                let next = Rc::new(self.threads_next(&threads, symbol));
+                if next.is_dead() {
+                    continue;
                 }
 
-                let dfa_state = dfa_states
-                    .entry(new_set)
-                    .or_insert_with_key(|k| dfa::State::new_node(k.contains(&self.end().borrow())))
-                    .clone();
-                dfa_states
-                    .get(&set)
-                    .unwrap()
-                    .borrow_mut()
-                    .transition(symbol, dfa_state);
+                let dfa_state = match dfa_states.get(&next) {
+                    Some(&id) => id,
+                    None => {
+                        CompileError::check_states(dfa.len(), max_states)?;
+                        let id = dfa.add_state(next.accepted);
+                        dfa_states.insert(next.clone(), id);
+                        queue.push_back(next);
+                        id
+                    }
+                };
+                dfa.transition(dfa_states[&threads], symbol, dfa_state);
+            }
+        }
+
+        Ok(dfa)
+    }
+
+    /// 搜索的初始状态，`anchored` 时只在输入起点起始匹配
+    pub(crate) fn threads_start(&self, anchored: bool) -> Threads {
+        let start = self.e_closure(&StateSet::from([self.start]));
+        self.settle(vec![start], !anchored)
+    }
+
+    /// 读入符号后的搜索状态，`symbol` 须为基本区间；仍在起始匹配时，新线程排在最后
+    pub(crate) fn threads_next(&self, threads: &Threads, symbol: Symbol) -> Threads {
+        let mut sets = threads
+            .sets
+            .iter()
+            .map(|set| self.e_closure(&self.move_to(set, symbol)))
+            .collect::<Vec<_>>();
+        if threads.seeding {
+            sets.push(self.e_closure(&StateSet::from([self.start])));
+        }
+        self.settle(sets, threads.seeding)
+    }
+
+    /// 去除重复的 NFA 状态，只保留起点最左者；若有集合可接受，删去其后的集合并停止起始匹配
+    fn settle(&self, sets: Vec<StateSet>, seeding: bool) -> Threads {
+        let mut seen = StateSet::new();
+        let mut threads = Threads {
+            sets: Vec::with_capacity(sets.len()),
+            seeding,
+            accepted: false,
+        };
+        for mut set in sets {
+            set.retain(|&id| seen.insert(id));
+            if set.is_empty() {
+                continue;
+            }
+            let accepted = !self.rules_of(&set).is_empty();
+            threads.sets.push(set);
+            if accepted {
+                threads.seeding = false;
+                threads.accepted = true;
+                break;
             }
         }
+        threads
+    }
 
-        DFA::new(dfa_start)
+    /// 搜索状态可接受的所有符号，仍在起始匹配时覆盖全部字节
+    fn threads_alphabet(&self, threads: &Threads) -> Vec<Symbol> {
+        let any = threads.seeding.then_some(Symbol::Range('\0', '\u{FF}'));
+        fsa::split_symbols(
+            threads
+                .sets
+                .iter()
+                .flatten()
+                .flat_map(|&id| self.state(id).transitions.keys().copied())
+                .filter(|symbol| !symbol.is_epsilon())
+                .chain(any),
+        )
+    }
+
+    /// 反转：所有转移反向，始态与终态互换，所接受的语言为原语言中各串的逆序
+    ///
+    /// 以 `accept` 标记的终态（如 `from_rules` 的结果）同样作为原终态：此时新增始态，
+    /// 经 ε 转移到达 `end` 及所有标记的状态；反转后各规则的编号不再保留
+    pub fn reverse(&self) -> NFA {
+        let mut nfa = NFA {
+            states: vec![State::default(); self.states.len()],
+            start: self.end,
+            end: self.start,
+            groups: self.groups.clone(),
+            bytes: self.bytes,
+        };
+        for (i, state) in self.states.iter().enumerate() {
+            for (symbol, target) in state.transitions() {
+                nfa.transition(target, symbol, StateID(i));
+            }
+        }
+
+        let accepting = (0..self.states.len())
+            .map(StateID)
+            .filter(|&id| self.state(id).rule.is_some())
+            .collect::<Vec<_>>();
+        if !accepting.is_empty() {
+            let start = nfa.add_state();
+            nfa.e_transition(start, self.end);
+            for id in accepting {
+                nfa.e_transition(start, id);
+            }
+            nfa.set_start(start);
+        }
+        nfa
+    }
+
+    /// 转换为字节模式：每个字符区间转移替换为其 UTF-8 编码的字节区间序列
+    ///
+    /// 新增的中间状态都不是终态，被接受的字节串均由完整的 UTF-8 序列连接而成，必定是合法的 UTF-8；
+    /// 自字符边界起匹配合法的 UTF-8 输入时，匹配的终点同样落在字符边界上；
+    /// 已是字节模式时原样返回
+    pub fn into_bytes(mut self) -> NFA {
+        if self.bytes {
+            return self;
+        }
+        for id in 0..self.states.len() {
+            let transitions = std::mem::take(&mut self.states[id].transitions);
+            for (symbol, targets) in transitions {
+                let Some((start, end)) = symbol.range() else {
+                    self.states[id].transitions.insert(symbol, targets);
+                    continue;
+                };
+                for sequence in Utf8Sequences::new(start, end) {
+                    let (last, prefix) = sequence.split_last().unwrap();
+                    let mut from = StateID(id);
+                    for &(lo, hi) in prefix {
+                        let next = self.add_state();
+                        self.transition(from, Symbol::Range(lo.into(), hi.into()), next);
+                        from = next;
+                    }
+                    for &target in &targets {
+                        self.transition(from, Symbol::Range(last.0.into(), last.1.into()), target);
+                    }
+                }
+            }
+        }
+        self.bytes = true;
+        self
+    }
+
+    /// 状态集接受的所有规则
+    #[inline]
+    pub(crate) fn rules_of(&self, set: &StateSet) -> RuleSet {
+        let end = set.contains(&self.end).then_some(0);
+        set.iter()
+            .filter_map(|&id| self.state(id).rule)
+            .chain(end)
+            .collect()
+    }
+
+    /// 状态集可接受的所有符号，拆分为互不相交的基本区间
+    #[inline]
+    fn alphabet(&self, set: &StateSet) -> Vec<Symbol> {
+        fsa::alphabet(set.iter().map(|&id| self.state(id)))
+    }
+
+    /// move 运算集，`symbol` 须为基本区间（见 `fsa::split_symbols`）
+    pub(crate) fn move_to(&self, set: &StateSet, symbol: Symbol) -> StateSet {
+        set.iter()
+            .flat_map(|&id| self.state(id).next_states(symbol))
+            .collect()
+    }
+
+    /// ε-closure 运算集，只保留其中的重要状态（含非 ε 转移、为终态或标记了规则）
+    ///
+    /// 仅有 ε 转移的状态不影响后续的转移与接受，略去后行为相同的状态集不再被区分
+    pub(crate) fn e_closure(&self, set: &StateSet) -> StateSet {
+        let mut closure = StateSet::new();
+        let mut queue = LinkedList::from_iter(set.iter().copied());
+        while let Some(id) = queue.pop_front() {
+            if !closure.insert(id) {
+                continue;
+            }
+            queue.extend(self.state(id).next_states(Symbol::Epsilon));
+        }
+        closure.retain(|&id| {
+            let state = self.state(id);
+            id == self.end
+                || state.rule.is_some()
+                || state.transitions.keys().any(|symbol| !symbol.is_epsilon())
+        });
+        closure
+    }
+}
+
+impl Default for NFA {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Debug for NFA {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut ids = HashSet::new();
-        let mut queue = LinkedList::from([self.start.clone()]);
-        while let Some(state) = queue.pop_front() {
-            let id = state.borrow().id;
-            if ids.contains(&id) {
+        let mut ids = StateSet::new();
+        let mut queue = LinkedList::from([self.start]);
+        while let Some(id) = queue.pop_front() {
+            if !ids.insert(id) {
                 continue;
             }
-            ids.insert(id);
 
-            for (symbol, targets) in &state.borrow().transitions {
-                for target in targets {
-                    queue.push_back(target.clone());
-                    write!(f, "[{:?}, {:?}] = {:?}\n", id, symbol, target.borrow().id)?;
+            for (symbol, targets) in &self.state(id).transitions {
+                for &target in targets {
+                    queue.push_back(target);
+                    writeln!(f, "[{:?}, {:?}] = {:?}", id, symbol, target)?;
                 }
             }
         }
@@ -86,92 +458,466 @@ impl Debug for NFA {
     }
 }
 
-pub type StateNode = fsa::StateNode<State>;
+/// 最左最长搜索中的 DFA 状态：按起点自左向右排列的线程（NFA 状态）集合
+///
+/// 同一 NFA 状态只保留在起点最左的集合中。某一位置出现匹配时，删去起点更靠右的集合并不再起始新线程，
+/// 此后的匹配只可能起点更左或同一起点更长，因此扫描中最后一次匹配的位置即为最左最长匹配的终点
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub(crate) struct Threads {
+    sets: Vec<StateSet>,
+    /// 是否仍在每个位置起始新线程
+    seeding: bool,
+    /// 当前位置是否为匹配的终点
+    accepted: bool,
+}
 
-/// NFA 状态
+impl Threads {
+    /// 当前位置是否为匹配的终点
+    #[inline]
+    pub(crate) fn accepted(&self) -> bool {
+        self.accepted
+    }
+
+    /// 不再有线程且不再起始新线程，此后不可能匹配
+    #[inline]
+    pub(crate) fn is_dead(&self) -> bool {
+        self.sets.is_empty() && !self.seeding
+    }
+
+    /// 所含 NFA 状态的数目
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.sets.iter().map(StateSet::len).sum()
+    }
+}
+
+/// Pike VM：以线程列表直接模拟字节模式的 Thompson NFA，无须确定化
+///
+/// 每个输入字节至多访问每个 NFA 状态一次，时间复杂度为 O(n·m)。
+/// 匹配语义与 `Matcher` 一致：整体为最左最长匹配；多条路径均能匹配时，
+/// 按 ε 转移的添加顺序取优先者，即选择左侧优先，闭包与可选尽可能多地重复
+///
+/// 线程列表等缓存在各次运行间复用，多个线程同时运行时各自使用一份；
+/// 只需匹配范围时线程仅记录起点，提取捕获分组时各线程共享分组位置，仅在 `Tag` 转移处复制
 #[derive(Debug)]
-pub struct State {
-    id: StateID,
-    transitions: HashMap<Symbol, LinkedList<StateNode>>,
+pub struct PikeVM {
+    nfa: NFA,
+    /// 各状态的转移，下标即状态 ID
+    steps: Vec<Step>,
+    caches: Pool<Cache>,
 }
 
-impl fsa::State for State {
+impl PikeVM {
+    /// 由字符模式的 NFA（如 `Regex::as_nfa` 的结果）构建
     #[inline]
-    fn id(&self) -> StateID {
-        self.id
+    pub fn new(nfa: NFA) -> Self {
+        Self::from_bytes(nfa.into_bytes())
     }
 
+    /// 由字节模式的 NFA 构建
     #[inline]
-    fn alphabet(&self) -> Box<dyn Iterator<Item = Symbol> + '_> {
-        Box::new(self.transitions.keys().copied())
+    pub(crate) fn from_bytes(nfa: NFA) -> Self {
+        let steps = nfa
+            .states
+            .iter()
+            .enumerate()
+            .map(|(id, state)| {
+                let mut step = Step {
+                    bytes: Vec::new(),
+                    epsilons: Vec::new(),
+                    is_thread: StateID(id) == nfa.end,
+                };
+                for (&symbol, targets) in &state.transitions {
+                    match (symbol, symbol.range()) {
+                        (_, Some((start, end))) => {
+                            let (start, end) = (start as u8, end as u8);
+                            step.bytes
+                                .extend(targets.iter().map(|&to| (start, end, to)));
+                            step.is_thread = true;
+                        }
+                        (Symbol::Tag(tag), None) => step
+                            .epsilons
+                            .extend(targets.iter().map(|&to| (Some(tag), to))),
+                        (_, None) => step.epsilons.extend(targets.iter().map(|&to| (None, to))),
+                    }
+                }
+                step
+            })
+            .collect();
+
+        Self {
+            nfa,
+            steps,
+            caches: Pool::new(),
+        }
+    }
+
+    /// 字节模式的 NFA
+    #[inline]
+    pub fn nfa(&self) -> &NFA {
+        &self.nfa
+    }
+
+    /// 整个输入是否匹配
+    #[inline]
+    pub fn is_match(&self, input: impl AsRef<[u8]>) -> bool {
+        let input = input.as_ref();
+        self.run(input, 0, true, false)
+            .is_some_and(|slots| slots[1] == Some(input.len()))
+    }
+
+    /// 查找最左最长匹配
+    #[inline]
+    pub fn find(&self, input: impl AsRef<[u8]>) -> Option<Match> {
+        self.find_at(input, 0)
+    }
+
+    /// 自 `start` 起查找最左最长匹配
+    #[inline]
+    pub fn find_at(&self, input: impl AsRef<[u8]>, start: usize) -> Option<Match> {
+        let slots = self.run(input.as_ref(), start, false, false)?;
+        Some(Match::new(slots[0]?, slots[1]?))
+    }
+
+    /// 查找最左最长匹配并提取捕获分组
+    #[inline]
+    pub fn captures(&self, input: impl AsRef<[u8]>) -> Option<Captures<'_>> {
+        let slots = self.run(input.as_ref(), 0, false, true)?;
+        Some(Captures::new(slots, self.nfa.groups()))
+    }
+
+    /// 已知 `span` 为最左最长匹配时，在其上锚定模拟以提取捕获分组
+    #[inline]
+    pub(crate) fn captures_in(&self, input: &[u8], span: Range<usize>) -> Option<Captures<'_>> {
+        let slots = self.run(&input[..span.end], span.start, true, true)?;
+        Some(Captures::new(slots, self.nfa.groups()))
+    }
+
+    /// 模拟 NFA，返回最左最长匹配的各分组位置（下标 `2g`、`2g + 1` 分别为分组 `g` 的起点、终点）
+    ///
+    /// 线程列表按起点升序、同起点按优先级排列，状态冲突时先到达的线程胜出。
+    /// `anchored` 时只从 `start` 处起始，否则在之后的每个位置都起始新线程，直到出现匹配；
+    /// `captures` 为 `false` 时只记录分组 0
+    fn run(&self, input: &[u8], start: usize, anchored: bool, captures: bool) -> Option<Slots> {
+        let nfa = &self.nfa;
+        self.caches.with(
+            || Cache::new(nfa.len()),
+            |cache| {
+                let matched = self.run_with(cache, input, start, anchored, captures);
+                cache.threads.clear();
+                cache.stack.clear();
+                matched
+            },
+        )
+    }
+
+    fn run_with(
+        &self,
+        cache: &mut Cache,
+        input: &[u8],
+        start: usize,
+        anchored: bool,
+        captures: bool,
+    ) -> Option<Slots> {
+        let nfa = &self.nfa;
+        let Cache {
+            threads,
+            next,
+            stack,
+            visited,
+        } = cache;
+        // 起点、终点及捕获分组的位置
+        let mut matched: Option<(usize, usize, Option<SharedSlots>)> = None;
+
+        visited.clear();
+        for position in start..=input.len() {
+            if matched.is_none() && (!anchored || position == start) {
+                let slots =
+                    captures.then(|| SharedSlots::from(vec![None; nfa.groups.len() * 2 + 2]));
+                let thread = Thread {
+                    id: nfa.start,
+                    start: position,
+                    slots,
+                };
+                self.add_thread(threads, stack, visited, thread, position);
+            }
+
+            // 到达终态的首个线程即为此位置上起点最左、优先级最高者
+            if let Some(thread) = threads.iter().find(|thread| thread.id == nfa.end) {
+                if matched.as_ref().is_none_or(|m| thread.start <= m.0) {
+                    matched = Some((thread.start, position, thread.slots.clone()));
+                }
+            }
+            if let Some(&(matched_start, ..)) = matched.as_ref() {
+                // 起点更靠右的线程不可能产生更优的匹配
+                threads.retain(|thread| thread.start <= matched_start);
+            }
+
+            if position == input.len() || (threads.is_empty() && (anchored || matched.is_some())) {
+                break;
+            }
+
+            let byte = input[position];
+            visited.clear();
+            for thread in threads.drain(..) {
+                for &(start, end, target) in &self.steps[thread.id.0].bytes {
+                    if !(start..=end).contains(&byte) {
+                        continue;
+                    }
+                    let next_thread = Thread {
+                        id: target,
+                        start: thread.start,
+                        slots: thread.slots.clone(),
+                    };
+                    self.add_thread(next, stack, visited, next_thread, position + 1);
+                }
+            }
+            std::mem::swap(threads, next);
+        }
+
+        let (start, end, slots) = matched?;
+        let mut slots = slots.map_or_else(|| vec![None; 2], |slots| slots.to_vec());
+        slots[0] = Some(start);
+        slots[1] = Some(end);
+        Some(slots)
+    }
+
+    /// 沿 ε 转移深度优先地加入线程，先访问到的线程优先级更高，`Tag` 转移记录当前位置；
+    /// 只有 ε 转移的状态不再读入字节，不加入线程列表
+    fn add_thread(
+        &self,
+        threads: &mut Vec<Thread>,
+        stack: &mut Vec<Thread>,
+        visited: &mut Visited,
+        thread: Thread,
+        position: usize,
+    ) {
+        stack.push(thread);
+        while let Some(thread) = stack.pop() {
+            if !visited.insert(thread.id) {
+                continue;
+            }
+
+            // 逆序入栈使先添加的转移先被访问
+            let step = &self.steps[thread.id.0];
+            for &(tag, id) in step.epsilons.iter().rev() {
+                let slots = match (tag, &thread.slots) {
+                    (Some(tag), Some(slots)) => {
+                        let mut slots = slots.to_vec();
+                        slots[tag] = Some(position);
+                        Some(SharedSlots::from(slots))
+                    }
+                    _ => thread.slots.clone(),
+                };
+                stack.push(Thread {
+                    id,
+                    start: thread.start,
+                    slots,
+                });
+            }
+            if step.is_thread {
+                threads.push(thread);
+            }
+        }
     }
 }
 
-impl State {
+/// `PikeVM` 中 NFA 状态的转移
+#[derive(Debug)]
+struct Step {
+    /// 字节区间转移 (起点, 终点, 目标)
+    bytes: Vec<(u8, u8, StateID)>,
+    /// ε 转移，`Tag` 转移带有其标记；Thompson 构造中每个状态至多含一种 ε 转移，按添加顺序排列
+    epsilons: Vec<(Option<usize>, StateID)>,
+    /// 是否读入字节或为终态，只有这样的状态才加入线程列表
+    is_thread: bool,
+}
+
+/// 线程：所在的 NFA 状态、匹配起点，以及提取捕获分组时的分组位置
+#[derive(Debug)]
+struct Thread {
+    id: StateID,
+    start: usize,
+    slots: Option<SharedSlots>,
+}
+
+/// 多个线程共享的分组位置，写入时复制
+type SharedSlots = Arc<[Option<usize>]>;
+
+/// `PikeVM` 在各次运行间复用的缓存
+#[derive(Debug)]
+struct Cache {
+    /// 当前位置的线程列表
+    threads: Vec<Thread>,
+    /// 读入下一个字节后的线程列表
+    next: Vec<Thread>,
+    /// 沿 ε 转移加入线程时的栈
+    stack: Vec<Thread>,
+    visited: Visited,
+}
+
+impl Cache {
     #[inline]
-    pub fn new() -> State {
-        State {
-            id: StateID::obtain(),
-            transitions: Default::default(),
+    fn new(len: usize) -> Self {
+        Self {
+            threads: Vec::new(),
+            next: Vec::new(),
+            stack: Vec::new(),
+            visited: Visited {
+                generations: vec![0; len],
+                generation: 0,
+            },
         }
     }
+}
 
-    /// 构建节点（包裹在 Rc 和 RefCell 中)
+/// 已访问的 NFA 状态集合：各状态记录其最近一次被访问时的代数，清空只需递增代数
+#[derive(Debug)]
+struct Visited {
+    generations: Vec<u32>,
+    generation: u32,
+}
+
+impl Visited {
     #[inline]
-    pub fn new_node() -> StateNode {
-        Rc::new(RefCell::new(State::new()))
+    fn clear(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            // 代数回绕时才真正清空
+            self.generations.fill(0);
+            self.generation = 1;
+        }
     }
 
-    /// ε 转移
+    /// 加入状态，此前已访问时返回 `false`
+    #[inline]
+    fn insert(&mut self, id: StateID) -> bool {
+        let generation = &mut self.generations[id.0];
+        if *generation == self.generation {
+            return false;
+        }
+        *generation = self.generation;
+        true
+    }
+}
+
+/// 捕获分组的位置，下标 `2g`、`2g + 1` 分别为分组 `g` 的起点、终点
+pub(crate) type Slots = Vec<Option<usize>>;
+
+/// NFA 状态
+#[derive(Clone, Debug, Default)]
+pub struct State {
+    transitions: HashMap<Symbol, Vec<StateID>>,
+    /// 接受的规则编号，见 `NFA::accept`
+    rule: Option<usize>,
+}
+
+impl fsa::State for State {
     #[inline]
-    pub fn e_transition(&mut self, target: StateNode) -> &mut Self {
-        self.transition(Symbol::Epsilon, target)
+    fn alphabet(&self) -> Box<dyn Iterator<Item = Symbol> + '_> {
+        Box::new(self.transitions.keys().copied())
     }
+}
 
-    /// 转移
+impl State {
+    /// 所有转移
     #[inline]
-    pub fn transition(&mut self, symbol: Symbol, target: StateNode) -> &mut Self {
+    pub fn transitions(&self) -> impl Iterator<Item = (Symbol, StateID)> + '_ {
         self.transitions
-            .entry(symbol)
-            .or_default()
-            .push_back(target);
-        self
+            .iter()
+            .flat_map(|(&symbol, targets)| targets.iter().map(move |&target| (symbol, target)))
     }
 
-    /// 根据符号获取接下来的状态集
+    /// 根据符号获取接下来的状态集，区间标记完整覆盖该符号的转移均被采纳
     #[inline]
-    fn next_states(&self, symbol: Symbol) -> Option<impl Iterator<Item = StateNode> + '_> {
-        self.transitions.get(&symbol).map(|s| s.iter().cloned())
+    fn next_states(&self, symbol: Symbol) -> impl Iterator<Item = StateID> + '_ {
+        self.transitions
+            .iter()
+            .filter(move |(label, _)| {
+                if symbol.is_epsilon() {
+                    label.is_epsilon()
+                } else {
+                    label.covers(symbol)
+                }
+            })
+            .flat_map(|(_, targets)| targets.iter().copied())
     }
 }
 
-type StateSet = fsa::StateSet<State>;
+#[cfg(test)]
+mod tests {
+    use crate::fsa::nfa::NFA;
+    use crate::fsa::{CompileError, XorShift};
+    use crate::regex::{parse, Regex};
+    use crate::{Captures, Matcher};
 
-impl StateSet {
-    /// move 运算集
-    fn move_to(&self, symbol: Symbol) -> Self {
-        let mut set = Self::new();
-        for state in self.states() {
-            if let Some(next_states) = state.borrow().next_states(symbol) {
-                set.extend(next_states);
-            }
+    /// `PikeVM` 与稠密 DFA 的匹配结果一致，只比较结果而不计时
+    fn check_agrees(pattern: &str, chars: &[char], max_len: usize, count: usize) {
+        let matcher = Matcher::from_regex(parse(pattern).unwrap());
+        assert!(matcher.dfa().is_some(), "{pattern}");
+        let vm = matcher.vm();
+
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+        for len in (0..count).map(|i| i * max_len / count) {
+            let haystack = rng.string(chars, len);
+            assert_eq!(
+                vm.is_match(&haystack),
+                matcher.is_matched(&haystack),
+                "{pattern}"
+            );
+            assert_eq!(vm.find(&haystack), matcher.find(&haystack), "{pattern}");
+
+            let groups = |captures: Captures| captures.iter().collect::<Vec<_>>();
+            assert_eq!(
+                vm.captures(&haystack).map(groups),
+                matcher.captures(&haystack).map(groups),
+                "{pattern} {haystack}"
+            );
         }
-        set
     }
 
-    /// ε-closure 运算集
-    fn e_closure(&self) -> Self {
-        let mut set = Self::new();
-        let mut queue = LinkedList::from_iter(self.states());
-        while let Some(state) = queue.pop_front() {
-            if set.contains(&state.borrow()) {
-                continue;
-            }
-            if let Some(states) = state.borrow().next_states(Symbol::Epsilon) {
-                queue.extend(states);
+    #[test]
+    fn pike_vm_agrees_with_dfa() {
+        for pattern in [
+            "",
+            "a*b|ab*",
+            "(ab|a)(bc|c)",
+            "((a|b)*)(a)(b)?",
+            "(?<x>b+)|a",
+            "[^a]中|é?",
+            "(a|中)+é",
+        ] {
+            check_agrees(pattern, &['a', 'b', 'c', '中', 'é'], 12, 200);
+        }
+    }
+
+    #[test]
+    fn pike_vm_agrees_with_dfa_on_long_input() {
+        let pattern = format!("(a|b)*a{}", "(a|b)".repeat(8));
+        check_agrees(&pattern, &['a', 'b'], 5_000, 20);
+        check_agrees("(a|b)*c(a)", &['a', 'b', 'c'], 5_000, 20);
+    }
+
+    #[test]
+    fn state_limit_is_never_exceeded() {
+        // 子集构造得到 2^6 个状态，搜索 DFA 的状态更多
+        let nfa = parse(&format!("(a|b)*a{}", "(a|b)".repeat(5)))
+            .unwrap()
+            .as_nfa()
+            .into_bytes();
+        for build in [NFA::as_dfa_with_limit, NFA::as_search_dfa_with_limit] {
+            let len = build(&nfa, usize::MAX).unwrap().len();
+            assert!(len >= 64);
+            for limit in [0, 1, len / 2, len - 1] {
+                assert_eq!(
+                    build(&nfa, limit).unwrap_err(),
+                    CompileError::TooManyStates {
+                        built: limit,
+                        limit
+                    }
+                );
             }
-            set.insert(state);
+            assert_eq!(build(&nfa, len).unwrap().len(), len);
         }
-        set
     }
 }
@@ -0,0 +1,157 @@
+/// 将字符区间拆分为若干 UTF-8 字节区间序列
+///
+/// 每个序列由逐字节的区间构成，如 `[a-z]` 对应 `[61-7A]`，`[\u{80}-\u{7FF}]` 对应
+/// `[C2-DF][80-BF]`，区间中每个字符的编码都恰好被其中一个序列接受
+pub(crate) struct Utf8Sequences {
+    stack: Vec<(u32, u32)>,
+}
+
+/// 各编码长度能表示的最大码点
+const MAX_OF_LEN: [u32; 4] = [0x7F, 0x7FF, 0xFFFF, 0x10FFFF];
+
+impl Utf8Sequences {
+    #[inline]
+    pub(crate) fn new(start: char, end: char) -> Self {
+        Self {
+            stack: vec![(start as u32, end as u32)],
+        }
+    }
+}
+
+impl Iterator for Utf8Sequences {
+    type Item = Vec<(u8, u8)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        'stack: while let Some((start, end)) = self.stack.pop() {
+            if start > end {
+                continue;
+            }
+
+            // 跨越代理区时拆分
+            if start < 0xD800 && end > 0xDFFF {
+                self.stack.push((0xE000, end));
+                self.stack.push((start, 0xD7FF));
+                continue;
+            }
+
+            // 跨越编码长度时拆分
+            for max in MAX_OF_LEN {
+                if start <= max && max < end {
+                    self.stack.push((max + 1, end));
+                    self.stack.push((start, max));
+                    continue 'stack;
+                }
+            }
+
+            // 按后续字节边界拆分，使每个字节位置都可独立表示为区间
+            let len = encoded_len(start);
+            for i in 1..len {
+                let mask = (1 << (6 * i)) - 1;
+                if start & !mask != end & !mask {
+                    if start & mask != 0 {
+                        self.stack.push(((start | mask) + 1, end));
+                        self.stack.push((start, start | mask));
+                        continue 'stack;
+                    }
+                    if end & mask != mask {
+                        self.stack.push((end & !mask, end));
+                        self.stack.push((start, (end & !mask) - 1));
+                        continue 'stack;
+                    }
+                }
+            }
+
+            let (start, end) = (encode(start), encode(end));
+            return Some(start.into_iter().zip(end).collect());
+        }
+        None
+    }
+}
+
+#[inline]
+fn encoded_len(code: u32) -> usize {
+    MAX_OF_LEN.iter().position(|&max| code <= max).unwrap() + 1
+}
+
+#[inline]
+fn encode(code: u32) -> Vec<u8> {
+    let char = char::from_u32(code).unwrap();
+    let mut buf = [0; 4];
+    char.encode_utf8(&mut buf).as_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Utf8Sequences;
+
+    /// 接受 `bytes` 的序列数目
+    fn matches(start: char, end: char, bytes: &[u8]) -> usize {
+        Utf8Sequences::new(start, end)
+            .filter(|sequence| {
+                sequence.len() == bytes.len()
+                    && sequence
+                        .iter()
+                        .zip(bytes)
+                        .all(|(&(low, high), byte)| (low..=high).contains(byte))
+            })
+            .count()
+    }
+
+    /// 各编码长度及代理区的边界附近的字符
+    fn boundaries() -> Vec<char> {
+        [
+            0, 1, 0x7E, 0x7F, 0x80, 0x81, 0xBF, 0xC0, 0x7FF, 0x800, 0x801, 0xFFF, 0x1000, 0xD7FF,
+            0xE000, 0xE001, 0xFFFD, 0xFFFF, 0x10000, 0x10001, 0x3FFFF, 0x40000, 0x10FFFE, 0x10FFFF,
+        ]
+        .into_iter()
+        .filter_map(char::from_u32)
+        .collect()
+    }
+
+    #[test]
+    fn each_char_matched_by_exactly_one_sequence() {
+        let chars = boundaries();
+        for &start in &chars {
+            for &end in chars.iter().filter(|&&end| end >= start) {
+                for &char in &chars {
+                    let mut buf = [0; 4];
+                    let bytes = char.encode_utf8(&mut buf).as_bytes();
+                    let expected = usize::from((start..=end).contains(&char));
+                    assert_eq!(
+                        matches(start, end, bytes),
+                        expected,
+                        "{start:?}..={end:?} {char:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_length_ranges() {
+        let sequences = |start, end| Utf8Sequences::new(start, end).collect::<Vec<_>>();
+        assert_eq!(sequences('a', 'z'), [vec![(0x61, 0x7A)]]);
+        assert_eq!(
+            sequences('\u{80}', '\u{7FF}'),
+            [vec![(0xC2, 0xDF), (0x80, 0xBF)]]
+        );
+        assert_eq!(
+            sequences('\u{10000}', '\u{10FFFF}'),
+            [
+                vec![(0xF0, 0xF0), (0x90, 0xBF), (0x80, 0xBF), (0x80, 0xBF)],
+                vec![(0xF1, 0xF3), (0x80, 0xBF), (0x80, 0xBF), (0x80, 0xBF)],
+                vec![(0xF4, 0xF4), (0x80, 0x8F), (0x80, 0xBF), (0x80, 0xBF)],
+            ]
+        );
+    }
+
+    #[test]
+    fn surrogates_are_never_encoded() {
+        // U+D800 与 U+DFFF 的“编码” ED A0 80、ED BF BF 不是合法的 UTF-8
+        for bytes in [[0xED, 0xA0, 0x80], [0xED, 0xBF, 0xBF]] {
+            assert_eq!(matches('\0', char::MAX, &bytes), 0);
+        }
+        assert_eq!(matches('\u{D7FF}', '\u{E000}', "\u{D7FF}".as_bytes()), 1);
+        assert_eq!(matches('\u{D7FF}', '\u{E000}', "\u{E000}".as_bytes()), 1);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::fsa::dense::DenseDFA;
+use crate::fsa::nfa::NFA;
+use crate::regex::Regex;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+/// 词法分析器
+///
+/// 所有规则合并为一个字节模式的最小稠密 DFA，各终态记录其接受的规则；
+/// 按最长匹配 (Maximal Munch) 切分输入，同样长度下先添加的规则优先
+#[derive(Clone, Debug)]
+pub struct Lexer<K> {
+    dfa: DenseDFA,
+    /// 规则编号对应的记号类型
+    kinds: Vec<K>,
+}
+
+impl<K: Clone> Lexer<K> {
+    /// 依次以 `(记号类型, 正规式)` 构建，排在前面的规则优先级更高
+    pub fn new<R: Regex>(rules: impl IntoIterator<Item = (K, R)>) -> Self {
+        rules
+            .into_iter()
+            .fold(Self::builder(), |builder, (kind, regex)| {
+                builder.rule(kind, regex)
+            })
+            .build()
+    }
+
+    #[inline]
+    pub fn builder() -> LexerBuilder<K> {
+        LexerBuilder { rules: Vec::new() }
+    }
+
+    /// 编译完成的稠密 DFA，状态接受的规则编号即规则的添加顺序
+    #[inline]
+    pub fn dfa(&self) -> &DenseDFA {
+        &self.dfa
+    }
+
+    /// 将输入切分为记号，遇到无法识别的位置时产生错误并停止
+    #[inline]
+    pub fn tokens<'l, 's>(&'l self, input: &'s str) -> Tokens<'l, 's, K> {
+        Tokens {
+            lexer: self,
+            input,
+            position: 0,
+            failed: false,
+        }
+    }
+
+    /// 自 `start` 起的最长非空匹配，返回其终点与接受的规则
+    fn longest_at(&self, input: &[u8], start: usize) -> Option<(usize, usize)> {
+        let mut state = self.dfa.start();
+        let mut last = None;
+        for (i, &byte) in input.iter().enumerate().skip(start) {
+            state = self.dfa.next_byte(state, byte);
+            if self.dfa.is_dead(state) {
+                break;
+            }
+            if let Some(rule) = self.dfa.rule(state) {
+                last = Some((i + 1, rule));
+            }
+        }
+        last
+    }
+}
+
+/// 词法分析器构建器，见 `Lexer::builder`
+pub struct LexerBuilder<K> {
+    rules: Vec<(K, NFA)>,
+}
+
+impl<K> LexerBuilder<K> {
+    /// 添加规则，优先级低于此前添加的所有规则
+    #[inline]
+    pub fn rule(mut self, kind: K, regex: impl Regex) -> Self {
+        self.rules.push((kind, regex.as_nfa()));
+        self
+    }
+
+    /// 合并所有规则，见 `NFA::from_rules`
+    pub fn build(self) -> Lexer<K> {
+        let (kinds, nfas): (Vec<_>, Vec<_>) = self.rules.into_iter().unzip();
+        let dfa = NFA::from_rules(nfas).into_bytes().as_dfa().minimize();
+        Lexer {
+            dfa: DenseDFA::from_dfa(&dfa),
+            kinds,
+        }
+    }
+}
+
+/// 记号
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Token<'s, K> {
+    kind: K,
+    span: Range<usize>,
+    text: &'s str,
+}
+
+impl<'s, K> Token<'s, K> {
+    #[inline]
+    pub fn kind(&self) -> &K {
+        &self.kind
+    }
+
+    /// 记号在输入中的字节范围
+    #[inline]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    #[inline]
+    pub fn text(&self) -> &'s str {
+        self.text
+    }
+}
+
+/// 词法错误，输入在 `position` 处无法匹配任何规则
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LexError {
+    position: usize,
+}
+
+impl LexError {
+    /// 无法识别的位置（字节偏移）
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl Display for LexError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "无法识别的记号，位于第 {} 字节", self.position)
+    }
+}
+
+impl Error for LexError {}
+
+/// 记号序列，见 `Lexer::tokens`
+pub struct Tokens<'l, 's, K> {
+    lexer: &'l Lexer<K>,
+    input: &'s str,
+    /// 下一个记号的起点
+    position: usize,
+    /// 已产生错误，此后不再产生记号
+    failed: bool,
+}
+
+impl<'s, K: Clone> Iterator for Tokens<'_, 's, K> {
+    type Item = Result<Token<'s, K>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.position >= self.input.len() {
+            return None;
+        }
+
+        let start = self.position;
+        let Some((end, rule)) = self.lexer.longest_at(self.input.as_bytes(), start) else {
+            self.failed = true;
+            return Some(Err(LexError { position: start }));
+        };
+
+        // 终点落在字符边界上，见 `NFA::into_bytes`
+        self.position = end;
+        Some(Ok(Token {
+            kind: self.lexer.kinds[rule].clone(),
+            span: start..end,
+            text: &self.input[start..end],
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LexError, Lexer};
+    use crate::regex::parse;
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    enum Kind {
+        If,
+        Ident,
+        Number,
+        Space,
+        Op,
+    }
+
+    /// 关键字在标识符之前；`keyword_first` 为 `false` 时顺序相反
+    fn lexer(keyword_first: bool) -> Lexer<Kind> {
+        let keyword = (Kind::If, parse("if").unwrap());
+        let ident = (Kind::Ident, parse("[a-z_][a-z_0-9]*|[一-龥]+").unwrap());
+        let rules = if keyword_first {
+            [keyword, ident]
+        } else {
+            [ident, keyword]
+        };
+        rules
+            .into_iter()
+            .fold(Lexer::builder(), |builder, (kind, regex)| {
+                builder.rule(kind, regex)
+            })
+            .rule(Kind::Number, parse("[0-9]+").unwrap())
+            .rule(Kind::Space, parse(" +").unwrap())
+            .rule(Kind::Op, parse("=|==|<|<=").unwrap())
+            .build()
+    }
+
+    fn kinds(lexer: &Lexer<Kind>, input: &str) -> Vec<(Kind, String)> {
+        lexer
+            .tokens(input)
+            .map(|token| {
+                let token = token.unwrap();
+                (*token.kind(), token.text().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn maximal_munch() {
+        let tokens = kinds(&lexer(true), "if iff<=if2==");
+        let expected = [
+            (Kind::If, "if"),
+            (Kind::Space, " "),
+            (Kind::Ident, "iff"),
+            (Kind::Op, "<="),
+            (Kind::Ident, "if2"),
+            (Kind::Op, "=="),
+        ];
+        assert_eq!(
+            tokens,
+            expected.map(|(kind, text)| (kind, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn earlier_rule_wins_on_equal_length() {
+        assert_eq!(kinds(&lexer(true), "if")[0].0, Kind::If);
+        assert_eq!(kinds(&lexer(false), "if")[0].0, Kind::Ident);
+        // 更长的匹配不受优先级影响
+        assert_eq!(kinds(&lexer(true), "ifx")[0].0, Kind::Ident);
+    }
+
+    #[test]
+    fn token_spans() {
+        let input = "变量 = 42";
+        let spans = lexer(true)
+            .tokens(input)
+            .map(|token| token.unwrap().span())
+            .collect::<Vec<_>>();
+        assert_eq!(spans, [0..6, 6..7, 7..8, 8..9, 9..11]);
+        for span in spans {
+            assert!(input.is_char_boundary(span.start) && input.is_char_boundary(span.end));
+        }
+    }
+
+    #[test]
+    fn error_position() {
+        let lexer = lexer(true);
+        let mut tokens = lexer.tokens("名 = $1");
+        for _ in 0..4 {
+            assert!(tokens.next().unwrap().is_ok());
+        }
+        let error = tokens.next().unwrap().unwrap_err();
+        assert_eq!(error, LexError { position: 6 });
+        assert_eq!(error.to_string(), "无法识别的记号，位于第 6 字节");
+        // 出错后不再产生记号
+        assert!(tokens.next().is_none());
+
+        let error = lexer.tokens("!").next().unwrap().unwrap_err();
+        assert_eq!(error.position(), 0);
+    }
+}
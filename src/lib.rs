@@ -1,32 +1,1009 @@
-#![feature(let_else)]
-
 pub mod fsa;
+pub mod lexer;
 pub mod regex;
 
-use crate::fsa::nfa::NFA;
-use crate::fsa::{dfa::DFA, Symbol};
+pub use crate::fsa::CompileError;
+
+use crate::fsa::nfa::{PikeVM, Slots, NFA};
+use crate::fsa::{dense::DenseDFA, dfa::DFA, lazy::LazyDFA, Pool};
 use crate::regex::Regex;
+use std::iter::Copied;
+use std::ops::Range;
+use std::slice;
+use std::sync::Arc;
+
+/// 匹配器，内部为字节模式的最小稠密 DFA，可同时匹配字符串与任意字节序列
+///
+/// 确定化后的状态数目超出上限时（如 `(a|b)*a(a|b)(a|b)…`），改为惰性 DFA 按需确定化，
+/// 各线程使用各自的缓存；单次搜索中缓存反复清空时则以 `PikeVM` 直接模拟 NFA，匹配结果均不变
+///
+/// 编译完成的自动机以 `Arc` 共享，因此 `Matcher` 满足 `Send + Sync`，克隆开销极小
+#[derive(Clone, Debug)]
+pub struct Matcher(Arc<Automata>);
+
+// 编译期保证 `Matcher` 可跨线程共享
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Matcher>();
+};
 
-pub struct Matcher(DFA);
+#[derive(Debug)]
+struct Automata {
+    engine: Engine,
+    /// 字节模式 NFA 的模拟器，用于提取捕获分组，以及惰性 DFA 放弃时的匹配
+    vm: PikeVM,
+}
+
+// 每个匹配器仅有一份，存放于 `Arc` 中，无须为缩小体积而装箱
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+enum Engine {
+    /// 预先确定化的稠密 DFA
+    Dense(Determinized),
+    /// 状态数目超出上限时，改为按需确定化
+    Lazy(LazyPool),
+}
+
+#[derive(Debug)]
+struct Determinized {
+    /// 正向 DFA，自起点锚定匹配
+    forward: DenseDFA,
+    /// 最左最长搜索的正向 DFA，用于寻找匹配终点，见 `NFA::as_search_dfa_with_limit`
+    search: DenseDFA,
+    /// 反向 DFA，自匹配终点向左锚定匹配，用于寻找匹配起点
+    reverse: DenseDFA,
+}
+
+/// 惰性的正向、搜索与反向 DFA，见 `Determinized`
+///
+/// 各方法在缓存反复清空而放弃时返回 `None`，由调用方改用 `PikeVM`
+#[derive(Debug)]
+struct LazyPair {
+    forward: LazyDFA,
+    search: LazyDFA,
+    reverse: LazyDFA,
+}
+
+/// 惰性 DFA 的缓存池：每次搜索取出一份缓存独占使用，用毕放回，多个线程可同时搜索
+///
+/// 池中缓存的数目不超过同时搜索的线程数，每份缓存的容量均为 `CompileOptions::cache_capacity`
+#[derive(Debug)]
+struct LazyPool {
+    /// 池为空时以此新建缓存，自身不用于搜索
+    template: LazyPair,
+    caches: Pool<LazyPair>,
+}
 
 impl Matcher {
     pub fn from_regex(regex: impl Regex) -> Self {
         Self::from_nfa(regex.as_nfa())
     }
 
+    /// 字符模式与字节模式（如 `NFA::into_bytes` 后确定化所得）的 DFA 均可，后者不再重新编码
     pub fn from_dfa(dfa: DFA) -> Self {
-        Self(dfa.minimize())
+        Self::from_nfa(dfa.as_nfa())
     }
 
+    /// 以默认选项构建，超出上限时改用惰性 DFA，因此不会失败
+    #[inline]
     pub fn from_nfa(nfa: NFA) -> Self {
-        Self::from_dfa(nfa.as_dfa().minimize())
+        Self::from_nfa_with(nfa, &CompileOptions::default())
+            .expect("允许改用惰性 DFA 时构建不会失败")
+    }
+
+    /// 按照选项构建匹配器，正规式中补、交、差运算的确定化同样受 `state_limit` 限制，
+    /// 超出时无法改用惰性 DFA，总是返回错误
+    #[inline]
+    pub fn from_regex_with(
+        regex: impl Regex,
+        options: &CompileOptions,
+    ) -> Result<Self, CompileError> {
+        Self::from_nfa_with(regex.try_as_nfa(options.state_limit)?, options)
+    }
+
+    /// 按照选项构建匹配器，确定化超出上限且不允许改用惰性 DFA 时返回错误
+    pub fn from_nfa_with(nfa: NFA, options: &CompileOptions) -> Result<Self, CompileError> {
+        let nfa = nfa.into_bytes();
+        let engine = match Determinized::new(&nfa, options) {
+            Ok(dfa) => Engine::Dense(dfa),
+            Err(_) if options.lazy_fallback => {
+                let lazy =
+                    |nfa, anchored| LazyDFA::from_bytes(nfa, anchored, options.cache_capacity);
+                Engine::Lazy(LazyPool::new(LazyPair {
+                    forward: lazy(nfa.clone(), true),
+                    search: lazy(nfa.clone(), false),
+                    reverse: lazy(nfa.reverse(), true),
+                }))
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self(Arc::new(Automata {
+            engine,
+            vm: PikeVM::from_bytes(nfa),
+        })))
+    }
+
+    /// 编译完成的稠密 DFA，改用惰性 DFA 时为 `None`
+    #[inline]
+    pub fn dfa(&self) -> Option<&DenseDFA> {
+        match &self.0.engine {
+            Engine::Dense(dfa) => Some(&dfa.forward),
+            Engine::Lazy(_) => None,
+        }
+    }
+
+    /// NFA 模拟器
+    #[inline]
+    pub fn vm(&self) -> &PikeVM {
+        &self.0.vm
+    }
+
+    /// 判断两者所匹配的语言是否相同，不同时返回区分串，见 `DFA::equivalent`
+    ///
+    /// 比较在字节 DFA 上进行，区分串按 UTF-8 字节数最短（同样长度下按字节字典序最小），
+    /// 按字符数计未必最短；需将两者的 NFA 完全确定化，不受 `CompileOptions` 的限制
+    pub fn same_language_as(&self, other: &Matcher) -> Result<(), String> {
+        let (dfa, other) = (self.0.vm.nfa().as_dfa(), other.0.vm.nfa().as_dfa());
+        dfa.equivalent(&other).map_err(|string| {
+            // 区分串被其中一者接受，必定是合法的 UTF-8，见 `NFA::into_bytes`
+            let bytes = string.chars().map(|c| c as u8).collect();
+            String::from_utf8(bytes).expect("字节模式的区分串应是合法的 UTF-8")
+        })
     }
 
     pub fn is_matched(&self, str: impl AsRef<str>) -> bool {
-        let symbols = str.as_ref().chars().map(Symbol::Char);
-        self.0
-            .end_of(symbols)
-            .map(|s| s.borrow().acceptable())
-            .unwrap_or_default()
+        self.is_matched_bytes(str.as_ref().as_bytes())
+    }
+
+    /// 匹配字节序列，字节序列无须是合法的 UTF-8
+    pub fn is_matched_bytes(&self, bytes: impl AsRef<[u8]>) -> bool {
+        match &self.0.engine {
+            Engine::Dense(dfa) => dfa.is_match(bytes.as_ref()),
+            Engine::Lazy(pool) => pool
+                .with(|lazy| lazy.is_match(bytes.as_ref()))
+                .unwrap_or_else(|| self.0.vm.is_match(bytes)),
+        }
+    }
+
+    /// 在字符串中查找最左最长匹配，返回其字节范围
+    #[inline]
+    pub fn find(&self, haystack: impl AsRef<str>) -> Option<Match> {
+        self.find_bytes(haystack.as_ref().as_bytes())
+    }
+
+    /// 在字节序列中查找最左最长匹配
+    ///
+    /// 先以搜索 DFA 自左向右扫描至死状态，最后一次到达终态的位置即为最左最长匹配的终点；
+    /// 再以反向 DFA 自该终点向左锚定扫描，最后一次到达终态的位置即为最左的起点。
+    /// 两次扫描均在不再可能匹配时停止，不必读完整个序列
+    #[inline]
+    pub fn find_bytes(&self, haystack: impl AsRef<[u8]>) -> Option<Match> {
+        self.find_at(haystack.as_ref(), 0)
+    }
+
+    /// 查找最左最长匹配并提取其中各捕获分组的范围
+    #[inline]
+    pub fn captures(&self, haystack: impl AsRef<str>) -> Option<Captures<'_>> {
+        self.captures_bytes(haystack.as_ref().as_bytes())
+    }
+
+    /// 在字节序列中查找最左最长匹配并提取捕获分组
+    ///
+    /// 匹配范围由 DFA 确定，再在该范围上锚定模拟 NFA 求出分组位置
+    pub fn captures_bytes(&self, haystack: impl AsRef<[u8]>) -> Option<Captures<'_>> {
+        let haystack = haystack.as_ref();
+        let m = self.find_bytes(haystack)?;
+        self.0.vm.captures_in(haystack, m.range())
+    }
+
+    /// 依次查找字符串中所有互不重叠的最左最长匹配
+    ///
+    /// 空匹配不会紧随上一个匹配的终点出现，且查找位置总是落在字符边界上
+    #[inline]
+    pub fn find_iter<'h>(&self, haystack: &'h str) -> Matches<'_, 'h> {
+        Matches::new(self, haystack.as_bytes(), true)
+    }
+
+    /// 依次查找字节序列中所有互不重叠的最左最长匹配
+    #[inline]
+    pub fn find_iter_bytes<'h>(&self, haystack: &'h [u8]) -> Matches<'_, 'h> {
+        Matches::new(self, haystack, false)
+    }
+
+    /// 自 `position` 起查找最左最长匹配
+    fn find_at(&self, haystack: &[u8], position: usize) -> Option<Match> {
+        match &self.0.engine {
+            Engine::Dense(dfa) => dfa.find_at(haystack, position),
+            Engine::Lazy(pool) => pool
+                .with(|lazy| lazy.find_at(haystack, position))
+                .unwrap_or_else(|| self.0.vm.find_at(haystack, position)),
+        }
+    }
+}
+
+impl Determinized {
+    /// 确定化并最小化各个 DFA，任一超出上限时返回错误
+    fn new(nfa: &NFA, options: &CompileOptions) -> Result<Self, CompileError> {
+        let limit = options.state_limit;
+        let dense = |dfa: DFA| DenseDFA::from_dfa_with_limit(&dfa.minimize(), options.memory_limit);
+
+        Ok(Self {
+            forward: dense(nfa.as_dfa_with_limit(limit)?)?,
+            search: dense(nfa.as_search_dfa_with_limit(limit)?)?,
+            reverse: dense(nfa.reverse().as_dfa_with_limit(limit)?)?,
+        })
+    }
+
+    fn is_match(&self, bytes: &[u8]) -> bool {
+        let dfa = &self.forward;
+        let mut state = dfa.start();
+        for &byte in bytes {
+            state = dfa.next_byte(state, byte);
+            if dfa.is_dead(state) {
+                return false;
+            }
+        }
+        dfa.acceptable(state)
+    }
+
+    /// 自 `position` 起查找最左最长匹配，见 `Matcher::find_bytes`
+    fn find_at(&self, haystack: &[u8], position: usize) -> Option<Match> {
+        let search = &self.search;
+        let mut state = search.start();
+        let mut end = search.acceptable(state).then_some(position);
+        for (i, &byte) in haystack[position..].iter().enumerate() {
+            state = search.next_byte(state, byte);
+            if search.is_dead(state) {
+                break;
+            }
+            if search.acceptable(state) {
+                end = Some(position + i + 1);
+            }
+        }
+        let end = end?;
+
+        let reverse = &self.reverse;
+        let mut state = reverse.start();
+        let mut start = reverse.acceptable(state).then_some(end);
+        for (i, &byte) in haystack[position..end].iter().enumerate().rev() {
+            state = reverse.next_byte(state, byte);
+            if reverse.is_dead(state) {
+                break;
+            }
+            if reverse.acceptable(state) {
+                start = Some(position + i);
+            }
+        }
+        Some(Match::new(start.expect("匹配终点之前必有起点"), end))
+    }
+}
+
+impl LazyPair {
+    /// 单次搜索中缓存被清空的次数上限，超过即放弃
+    const MAX_CACHE_CLEARS: usize = 8;
+
+    /// 共享同一 NFA、缓存为空的新实例
+    #[inline]
+    fn fresh(&self) -> Self {
+        Self {
+            forward: self.forward.fresh(),
+            search: self.search.fresh(),
+            reverse: self.reverse.fresh(),
+        }
+    }
+
+    fn is_match(&mut self, bytes: &[u8]) -> Option<bool> {
+        let mut matched = false;
+        let completed = self.forward.try_scan(
+            bytes.iter().copied(),
+            |n| matched = n == bytes.len(),
+            Self::MAX_CACHE_CLEARS,
+        );
+        completed.then_some(matched)
+    }
+
+    fn find_at(&mut self, haystack: &[u8], position: usize) -> Option<Option<Match>> {
+        let mut end = None;
+        let completed = self.search.try_scan(
+            haystack[position..].iter().copied(),
+            |n| end = Some(position + n),
+            Self::MAX_CACHE_CLEARS,
+        );
+        if !completed {
+            return None;
+        }
+        let Some(end) = end else {
+            return Some(None);
+        };
+
+        let mut start = None;
+        let completed = self.reverse.try_scan(
+            haystack[position..end].iter().rev().copied(),
+            |n| start = Some(end - n),
+            Self::MAX_CACHE_CLEARS,
+        );
+        completed.then(|| start.map(|start| Match::new(start, end)))
+    }
+}
+
+impl LazyPool {
+    #[inline]
+    fn new(template: LazyPair) -> Self {
+        Self {
+            template,
+            caches: Pool::new(),
+        }
+    }
+
+    /// 取出一份缓存（池为空时新建）执行 `f`，完成后放回
+    #[inline]
+    fn with<T>(&self, f: impl FnOnce(&mut LazyPair) -> T) -> T {
+        self.caches.with(|| self.template.fresh(), f)
+    }
+}
+
+/// 匹配器的构建选项，用于限制确定化的开销，以安全地处理来源不可信的正则表达式
+///
+/// ```text
+/// let options = CompileOptions::new().state_limit(1_000).lazy_fallback(false);
+/// let matcher = Matcher::from_regex_with(regex, &options)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompileOptions {
+    /// 确定化时 DFA 状态数目的上限
+    state_limit: usize,
+    /// 稠密 DFA 占用内存（字节）的上限
+    memory_limit: usize,
+    /// 超出上限时是否改用惰性 DFA，否则返回错误
+    lazy_fallback: bool,
+    /// 惰性 DFA 的缓存容量（字节）
+    cache_capacity: usize,
+}
+
+impl CompileOptions {
+    /// 默认的 DFA 状态数目上限
+    pub const DEFAULT_STATE_LIMIT: usize = 10_000;
+
+    /// 默认的稠密 DFA 内存上限（字节）
+    pub const DEFAULT_MEMORY_LIMIT: usize = 16 << 20;
+
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            state_limit: Self::DEFAULT_STATE_LIMIT,
+            memory_limit: Self::DEFAULT_MEMORY_LIMIT,
+            lazy_fallback: true,
+            cache_capacity: LazyDFA::DEFAULT_CACHE_CAPACITY,
+        }
+    }
+
+    /// DFA 状态数目上限，正向、搜索与反向 DFA 分别计算
+    #[inline]
+    pub fn state_limit(mut self, limit: usize) -> Self {
+        self.state_limit = limit;
+        self
+    }
+
+    /// 稠密 DFA 内存上限（字节），正向、搜索与反向 DFA 分别计算
+    #[inline]
+    pub fn memory_limit(mut self, limit: usize) -> Self {
+        self.memory_limit = limit;
+        self
+    }
+
+    /// 超出上限时是否改用惰性 DFA，默认为 `true`；为 `false` 时返回 `CompileError`
+    #[inline]
+    pub fn lazy_fallback(mut self, yes: bool) -> Self {
+        self.lazy_fallback = yes;
+        self
+    }
+
+    /// 惰性 DFA 的缓存容量（字节）
+    #[inline]
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+}
+
+impl Default for CompileOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 多模式匹配器，所有模式合并为一个字节模式的最小稠密 DFA，一次扫描即得出全部匹配的模式
+///
+/// DFA 状态记录其接受的模式编号集，编号即模式在构建时的顺序
+#[derive(Clone, Debug)]
+pub struct MatcherSet(Arc<MatcherSetInner>);
+
+#[derive(Debug)]
+struct MatcherSetInner {
+    dfa: DenseDFA,
+    /// 模式数目
+    len: usize,
+}
+
+impl MatcherSet {
+    pub fn new<R: Regex>(regexes: impl IntoIterator<Item = R>) -> Self {
+        Self::from_nfas(regexes.into_iter().map(|regex| regex.as_nfa()))
+    }
+
+    pub fn from_nfas(nfas: impl IntoIterator<Item = NFA>) -> Self {
+        let nfas = nfas.into_iter().collect::<Vec<_>>();
+        let len = nfas.len();
+        let dfa = NFA::from_rules(nfas).into_bytes().as_dfa().minimize();
+
+        Self(Arc::new(MatcherSetInner {
+            dfa: DenseDFA::from_dfa(&dfa),
+            len,
+        }))
+    }
+
+    /// 编译完成的稠密 DFA
+    #[inline]
+    pub fn dfa(&self) -> &DenseDFA {
+        &self.0.dfa
+    }
+
+    /// 模式数目
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.len == 0
+    }
+
+    /// 是否至少匹配一个模式
+    #[inline]
+    pub fn is_matched(&self, str: impl AsRef<str>) -> bool {
+        self.matches(str).next().is_some()
+    }
+
+    /// 字符串完整匹配的所有模式编号，升序排列
+    #[inline]
+    pub fn matches(&self, str: impl AsRef<str>) -> PatternIDs<'_> {
+        self.matches_bytes(str.as_ref().as_bytes())
+    }
+
+    /// 字节序列完整匹配的所有模式编号，升序排列
+    pub fn matches_bytes(&self, bytes: impl AsRef<[u8]>) -> PatternIDs<'_> {
+        let dfa = &self.0.dfa;
+        let mut state = dfa.start();
+        for &byte in bytes.as_ref() {
+            state = dfa.next_byte(state, byte);
+            if dfa.is_dead(state) {
+                break;
+            }
+        }
+        dfa.rules(state).iter().copied()
+    }
+}
+
+/// 匹配的模式编号，见 `MatcherSet::matches`
+pub type PatternIDs<'s> = Copied<slice::Iter<'s, usize>>;
+
+/// 匹配结果，记录匹配部分在输入中的字节范围
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Match {
+    start: usize,
+    end: usize,
+}
+
+impl Match {
+    #[inline]
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// 捕获分组的匹配结果，分组 0 为整个匹配，其余分组按左括号出现的顺序编号
+#[derive(Clone, Debug)]
+pub struct Captures<'m> {
+    slots: Slots,
+    /// 分组 1、2… 的名称
+    names: &'m [Option<String>],
+}
+
+impl<'m> Captures<'m> {
+    #[inline]
+    pub(crate) fn new(slots: Slots, names: &'m [Option<String>]) -> Self {
+        Self { slots, names }
+    }
+
+    /// 第 `index` 个分组的范围，分组未参与匹配时为 `None`
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<Match> {
+        let start = (*self.slots.get(index * 2)?)?;
+        let end = (*self.slots.get(index * 2 + 1)?)?;
+        Some(Match::new(start, end))
+    }
+
+    /// 命名分组的范围，同名分组取第一个
+    #[inline]
+    pub fn name(&self, name: &str) -> Option<Match> {
+        let index = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(index + 1)
+    }
+
+    /// 依次为各分组的范围（含分组 0）
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match>> + '_ {
+        (0..self.slots.len() / 2).map(|index| self.get(index))
+    }
+}
+
+/// 所有互不重叠的匹配，见 `Matcher::find_iter`
+///
+/// 每个匹配都自上一次的查找位置起按 `Matcher::find_bytes` 的方式查找，除输入外只需常数的额外内存
+pub struct Matches<'m, 'h> {
+    matcher: &'m Matcher,
+    haystack: &'h [u8],
+    /// 下一次查找的位置
+    position: usize,
+    /// 上一个匹配的终点
+    last_end: Option<usize>,
+    /// 输入为合法 UTF-8 时，查找位置只落在字符边界上
+    utf8: bool,
+}
+
+impl<'m, 'h> Matches<'m, 'h> {
+    #[inline]
+    fn new(matcher: &'m Matcher, haystack: &'h [u8], utf8: bool) -> Self {
+        Self {
+            matcher,
+            haystack,
+            position: 0,
+            last_end: None,
+            utf8,
+        }
+    }
+
+    /// `position` 之后的下一个查找位置
+    #[inline]
+    fn step(&self, position: usize) -> usize {
+        let mut next = position + 1;
+        if self.utf8 {
+            // UTF-8 后续字节形如 0b10xx_xxxx
+            while next < self.haystack.len() && self.haystack[next] & 0xC0 == 0x80 {
+                next += 1;
+            }
+        }
+        next
+    }
+}
+
+impl Iterator for Matches<'_, '_> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.position <= self.haystack.len() {
+            let m = self.matcher.find_at(self.haystack, self.position)?;
+
+            if m.is_empty() && self.last_end == Some(m.end()) {
+                // 跳过紧随上一个匹配的空匹配
+                self.position = self.step(m.start());
+                continue;
+            }
+
+            self.position = if m.is_empty() {
+                self.step(m.end())
+            } else {
+                m.end()
+            };
+            self.last_end = Some(m.end());
+            return Some(m);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompileError, CompileOptions, Engine, Match, Matcher, MatcherSet};
+    use crate::fsa::XorShift;
+    use crate::regex::{parse, Regex};
+    use std::ops::Range;
+
+    /// 同一模式分别以稠密 DFA、惰性 DFA 及缓存容量为 0 的惰性 DFA（输入稍长即改用 `PikeVM`）匹配
+    fn engines(pattern: &str) -> [Matcher; 3] {
+        let matcher = |options: CompileOptions| {
+            Matcher::from_regex_with(parse(pattern).unwrap(), &options).unwrap()
+        };
+        let engines = [
+            matcher(CompileOptions::new()),
+            matcher(CompileOptions::new().state_limit(0)),
+            matcher(CompileOptions::new().state_limit(0).cache_capacity(0)),
+        ];
+        assert!(engines[0].dfa().is_some() && engines[1].dfa().is_none());
+        engines
+    }
+
+    fn check_find(cases: &[(&str, &str, Option<Range<usize>>)]) {
+        for (pattern, haystack, expected) in cases {
+            for matcher in engines(pattern) {
+                let found = matcher.find(haystack).map(|m| m.range());
+                assert_eq!(&found, expected, "{pattern} {haystack}");
+            }
+        }
+    }
+
+    #[test]
+    fn find_leftmost_longest() {
+        check_find(&[
+            ("abcd|c", "abcd", Some(0..4)),
+            ("ab|bcde", "abcde", Some(0..2)),
+            ("a|ab|abc", "xabcab", Some(1..4)),
+            ("a+", "baaab", Some(1..4)),
+            ("(a|b)*abb", "cababbabbc", Some(1..9)),
+            ("x", "abc", None),
+        ]);
+    }
+
+    #[test]
+    fn find_empty_match() {
+        check_find(&[
+            ("a*", "bbb", Some(0..0)),
+            ("a*", "", Some(0..0)),
+            ("a*", "baa", Some(0..0)),
+            ("b?a*", "cba", Some(0..0)),
+            ("a*b", "", None),
+        ]);
+    }
+
+    #[test]
+    fn find_next_to_multibyte_chars() {
+        check_find(&[
+            ("中+", "a中中b", Some(1..7)),
+            ("b", "中b", Some(3..4)),
+            (".", "中", Some(0..3)),
+            ("[^a]+", "a中é", Some(1..6)),
+            ("é|e", "中é", Some(3..5)),
+            ("é", "中e", None),
+        ]);
+    }
+
+    #[test]
+    fn find_bytes_in_invalid_utf8() {
+        for matcher in engines("a+") {
+            assert_eq!(matcher.find_bytes(b"\xFFaa\xE4"), Some(Match::new(1, 3)));
+        }
+    }
+
+    #[test]
+    fn from_dfa_in_either_mode() {
+        let nfa = parse("中+|é|a").unwrap().as_nfa();
+        let bytes = nfa.clone().into_bytes();
+        assert!(!nfa.is_bytes() && bytes.is_bytes());
+        assert!(bytes.clone().into_bytes().as_dfa().is_bytes());
+
+        // 字节模式的 DFA 不再重新编码，否则超出 ASCII 的字符都无法匹配
+        for dfa in [nfa.as_dfa(), bytes.as_dfa(), bytes.as_dfa().minimize()] {
+            let matcher = Matcher::from_dfa(dfa);
+            for (haystack, matched) in [("中中", true), ("é", true), ("a", true), ("中é", false)]
+            {
+                assert_eq!(matcher.is_matched(haystack), matched, "{haystack}");
+            }
+            assert!(!matcher.is_matched_bytes(b"\xE4"));
+        }
+    }
+
+    fn check_find_iter(pattern: &str, haystack: &str, expected: &[Range<usize>]) {
+        for matcher in engines(pattern) {
+            let found = matcher
+                .find_iter(haystack)
+                .map(|m| m.range())
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected, "{pattern} {haystack}");
+        }
+    }
+
+    #[test]
+    fn find_iter_adjacent_matches() {
+        check_find_iter("ab", "ababxab", &[0..2, 2..4, 5..7]);
+        check_find_iter("a+|b", "aabaab", &[0..2, 2..3, 3..5, 5..6]);
+        check_find_iter("中", "中中文中", &[0..3, 3..6, 9..12]);
+        check_find_iter("x", "abc", &[]);
+    }
+
+    #[test]
+    fn find_iter_empty_matches_step_over_chars() {
+        check_find_iter("", "中é", &[0..0, 3..3, 5..5]);
+        check_find_iter("a*", "中a文", &[0..0, 3..4, 7..7]);
+        check_find_iter("a*", "baab", &[0..0, 1..3, 4..4]);
+        check_find_iter("中?", "中文", &[0..3, 6..6]);
+    }
+
+    #[test]
+    fn find_iter_bytes_steps_by_byte() {
+        for matcher in engines("a*") {
+            let found = matcher
+                .find_iter_bytes("中a".as_bytes())
+                .map(|m| m.range())
+                .collect::<Vec<_>>();
+            assert_eq!(found, [0..0, 1..1, 2..2, 3..4]);
+        }
+    }
+
+    fn set(patterns: &[&str]) -> MatcherSet {
+        MatcherSet::new(patterns.iter().map(|pattern| parse(pattern).unwrap()))
+    }
+
+    #[test]
+    fn matcher_set_reports_every_overlapping_pattern() {
+        let set = set(&["[a-z]+", "foo", "f.*", "[0-9]+", "中.?"]);
+        assert_eq!(set.len(), 5);
+        for (input, expected) in [
+            ("foo", &[0, 1, 2][..]),
+            ("fo", &[0, 2]),
+            ("f9", &[2]),
+            ("bar", &[0]),
+            ("42", &[3]),
+            ("中", &[4]),
+            ("中文", &[4]),
+            ("", &[]),
+            ("FOO", &[]),
+        ] {
+            assert_eq!(set.matches(input).collect::<Vec<_>>(), expected, "{input}");
+            assert_eq!(set.is_matched(input), !expected.is_empty(), "{input}");
+        }
+    }
+
+    #[test]
+    fn matcher_set_keeps_equivalent_patterns_apart() {
+        // 语言相同的模式在最小化时不能合并，各自的编号都要保留
+        let set = set(&["a+", "aa*", "b", "(a|b)*", "a(a)*"]);
+        assert_eq!(set.matches("a").collect::<Vec<_>>(), [0, 1, 3, 4]);
+        assert_eq!(set.matches("aaa").collect::<Vec<_>>(), [0, 1, 3, 4]);
+        assert_eq!(set.matches("b").collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(set.matches("ab").collect::<Vec<_>>(), [3]);
+        assert_eq!(set.matches("").collect::<Vec<_>>(), [3]);
+        assert_eq!(set.matches_bytes(b"a\xFF").count(), 0);
+    }
+
+    fn check_captures(pattern: &str, haystack: &str, expected: &[Option<Range<usize>>]) {
+        for matcher in engines(pattern) {
+            let captures = matcher.captures(haystack).unwrap();
+            let groups = captures
+                .iter()
+                .map(|m| m.map(|m| m.range()))
+                .collect::<Vec<_>>();
+            assert_eq!(groups, expected, "{pattern} {haystack}");
+        }
+    }
+
+    #[test]
+    fn captures_nested_groups() {
+        check_captures(
+            "((a)(b(c)))",
+            "xabc",
+            &[Some(1..4), Some(1..4), Some(1..2), Some(2..4), Some(3..4)],
+        );
+    }
+
+    #[test]
+    fn captures_last_iteration_wins() {
+        check_captures("(a|b)*", "abba", &[Some(0..4), Some(3..4)]);
+        check_captures("(ab)+c", "ababc", &[Some(0..5), Some(2..4)]);
+        // 各分组保留其最后一次参与匹配时的位置
+        check_captures("(?:(a)|(b))+", "ab", &[Some(0..2), Some(0..1), Some(1..2)]);
+    }
+
+    #[test]
+    fn captures_non_participating_group() {
+        check_captures("(a)|(b)", "b", &[Some(0..1), None, Some(0..1)]);
+        check_captures("x(y)?", "x", &[Some(0..1), None]);
+        check_captures("(a)*", "bb", &[Some(0..0), None]);
+        for matcher in engines("(a)|(b)") {
+            let captures = matcher.captures("b").unwrap();
+            assert_eq!(captures.get(1), None);
+            assert_eq!(captures.get(3), None);
+        }
+    }
+
+    #[test]
+    fn captures_by_name() {
+        for matcher in engines("(?<year>\\d+)-(?P<month>\\d+)(-(?<day>\\d+))?") {
+            let captures = matcher.captures("at 2024-05").unwrap();
+            assert_eq!(captures.name("year"), Some(Match::new(3, 7)));
+            assert_eq!(captures.name("month"), Some(Match::new(8, 10)));
+            assert_eq!(captures.name("day"), None);
+            assert_eq!(captures.name("hour"), None);
+        }
+    }
+
+    #[test]
+    fn captures_next_to_multibyte_chars() {
+        check_captures("(中+)(.)", "a中中é!", &[Some(1..9), Some(1..7), Some(7..9)]);
+        check_captures("(é?)(b)", "中éb", &[Some(3..6), Some(3..5), Some(5..6)]);
+    }
+
+    #[test]
+    fn lazy_dfa_with_tiny_cache_agrees_with_dense() {
+        let pattern = format!("(a|b)*a{}", "(a|b)".repeat(6));
+        let matcher = |options: &CompileOptions| {
+            Matcher::from_regex_with(parse(&pattern).unwrap(), options).unwrap()
+        };
+        let dense = matcher(&CompileOptions::new());
+        let lazy = matcher(&CompileOptions::new().state_limit(0));
+        let tiny = matcher(&CompileOptions::new().state_limit(0).cache_capacity(4 << 10));
+
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+        let haystack = (0..3_000)
+            .map(|i| {
+                // 末尾的 `c` 使整个输入不匹配
+                match (i, rng.below(2)) {
+                    (2_999, _) => 'c',
+                    (_, 0) => 'a',
+                    _ => 'b',
+                }
+            })
+            .collect::<String>();
+
+        // 缓存容量充足时惰性 DFA 完成搜索；过小时缓存反复清空，惰性 DFA 放弃，改用 `PikeVM`
+        for (matcher, completed) in [(&lazy, true), (&tiny, false)] {
+            let Engine::Lazy(pool) = &matcher.0.engine else {
+                panic!("应改用惰性 DFA");
+            };
+            let bytes = haystack.as_bytes();
+            assert_eq!(pool.with(|lazy| lazy.is_match(bytes)).is_some(), completed);
+            assert_eq!(
+                pool.with(|lazy| lazy.find_at(bytes, 0)).is_some(),
+                completed
+            );
+        }
+
+        for end in [0, 7, 100, 2_999, 3_000] {
+            let haystack = &haystack[..end];
+            let expected = dense.find_iter(haystack).collect::<Vec<_>>();
+            for matcher in [&lazy, &tiny] {
+                assert_eq!(matcher.is_matched(haystack), dense.is_matched(haystack));
+                assert_eq!(matcher.find(haystack), dense.find(haystack));
+                assert_eq!(matcher.find_iter(haystack).collect::<Vec<_>>(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn state_limit_without_lazy_fallback() {
+        let pattern = format!("(a|b)*a{}", "(a|b)".repeat(5));
+        let build =
+            |options: &CompileOptions| Matcher::from_regex_with(parse(&pattern).unwrap(), options);
+
+        let options = CompileOptions::new().state_limit(16).lazy_fallback(false);
+        assert_eq!(
+            build(&options).unwrap_err(),
+            CompileError::TooManyStates {
+                built: 16,
+                limit: 16
+            }
+        );
+        let options = CompileOptions::new().memory_limit(64).lazy_fallback(false);
+        assert!(matches!(
+            build(&options),
+            Err(CompileError::TooMuchMemory { limit: 64, .. })
+        ));
+
+        // 允许改用惰性 DFA 时同样的选项构建成功
+        let matcher = build(&CompileOptions::new().state_limit(16)).unwrap();
+        assert!(matcher.dfa().is_none());
+        assert_eq!(matcher.find("bbab"), None);
+        assert_eq!(matcher.find("babbbbb").map(|m| m.range()), Some(0..7));
+    }
+
+    #[test]
+    fn not_exceeding_state_limit() {
+        // 确定化得到 2^6 个状态
+        let regex = || parse(&format!("(a|b)*a{}", "(a|b)".repeat(5))).unwrap();
+        let error = CompileError::TooManyStates {
+            built: 16,
+            limit: 16,
+        };
+
+        // 补运算无法改用惰性 DFA，是否允许都返回错误；嵌套时内层同样受限
+        for lazy_fallback in [true, false] {
+            let options = CompileOptions::new()
+                .state_limit(16)
+                .lazy_fallback(lazy_fallback);
+            let not = Matcher::from_regex_with(regex().not(), &options);
+            assert_eq!(not.unwrap_err(), error);
+            let not_not = Matcher::from_regex_with(regex().not().not(), &options);
+            assert_eq!(not_not.unwrap_err(), error);
+        }
+
+        let matcher = Matcher::from_regex_with(regex().not(), &CompileOptions::new()).unwrap();
+        assert!(matcher.is_matched("bbbbbb"));
+        assert!(!matcher.is_matched("babbbbb"));
+    }
+
+    #[test]
+    fn product_exceeding_state_limit() {
+        // `a` 的个数是 5 的倍数、`b` 的个数是 7 的倍数，各自的 DFA 很小，积却有 35 个状态
+        let left = || parse("(b*ab*ab*ab*ab*a)*b*").unwrap();
+        let right = || parse("(a*ba*ba*ba*ba*ba*ba*b)*a*").unwrap();
+        fn len(regex: impl Regex) -> usize {
+            regex.as_nfa().as_dfa().len()
+        }
+        let limit = len(left()).max(len(right()));
+        assert!(limit < len(left().both(right())));
+        assert!(limit < len(left().except(right())));
+
+        let options = CompileOptions::new().state_limit(limit);
+        let error = CompileError::TooManyStates {
+            built: limit,
+            limit,
+        };
+        let both = Matcher::from_regex_with(left().both(right()), &options);
+        assert_eq!(both.unwrap_err(), error);
+        let except = Matcher::from_regex_with(left().except(right()), &options);
+        assert_eq!(except.unwrap_err(), error);
+
+        let options = CompileOptions::new();
+        let both = Matcher::from_regex_with(left().both(right()), &options).unwrap();
+        let except = Matcher::from_regex_with(left().except(right()), &options).unwrap();
+        let haystack = format!("{}{}", "ab".repeat(5), "b".repeat(2));
+        assert!(both.is_matched(&haystack) && !except.is_matched(&haystack));
+        let haystack = format!("{haystack}bb");
+        assert!(!both.is_matched(&haystack) && except.is_matched(&haystack));
+    }
+
+    #[test]
+    fn same_language_as() {
+        let matcher = |pattern| Matcher::from_regex(parse(pattern).unwrap());
+        assert_eq!(
+            matcher("(a|b)*").same_language_as(&matcher("(b|a)*")),
+            Ok(())
+        );
+        assert_eq!(
+            matcher("é|a").same_language_as(&matcher("a")),
+            Err("é".to_string())
+        );
+        assert_eq!(
+            matcher("[a-z]x").same_language_as(&matcher("[b-z]x")),
+            Err("ax".to_string())
+        );
+        // 区分串在字节 DFA 上求得，多字节的字符仍完整地出现
+        assert_eq!(
+            matcher("[à-ÿ]").same_language_as(&matcher("[á-ÿ]")),
+            Err("à".to_string())
+        );
+        // 不受 `CompileOptions` 的限制，改用惰性 DFA 的匹配器同样可以比较
+        let [dense, lazy, _] = engines("(a|b)*a(a|b)");
+        assert_eq!(dense.same_language_as(&lazy), Ok(()));
+        assert_eq!(
+            lazy.same_language_as(&matcher("(a|b)*b(a|b)")),
+            Err("aa".to_string())
+        );
     }
 }
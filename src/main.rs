@@ -18,7 +18,7 @@ fn main() {
         // 获取 DFA 经过所有符号后所到达的状态
         .end_of(symbols)
         // 判断此时到达的状态是否为状态（可接受状态）
-        .map(|s| s.borrow().acceptable())
+        .map(|s| dfa.state(s).acceptable())
         .unwrap_or_default();
 
     println!("{}", is_matched);
@@ -0,0 +1,173 @@
+use crate::fsa::nfa::NFA;
+use crate::fsa::CompileError;
+use crate::regex::tokens::{
+    Alternative, Any, Char, Class, Closure, Concatenation, Difference, Empty, Group, Intersection,
+    Not, Optional, Some,
+};
+use crate::regex::Regex;
+use std::fmt::{Debug, Formatter};
+
+/// 动态正规式，运行期组合而成（如由字符串解析得到）
+#[derive(Clone)]
+pub enum Expr {
+    Empty(Empty),
+    Char(Char),
+    /// 字符串，即若干字符依次连接
+    Literal(String),
+    Class(Class),
+    Any(Any),
+    Concatenation(Box<Concatenation<Expr, Expr>>),
+    Alternative(Box<Alternative<Expr, Expr>>),
+    Intersection(Box<Intersection<Expr, Expr>>),
+    Difference(Box<Difference<Expr, Expr>>),
+    Closure(Box<Closure<Expr>>),
+    Some(Box<Some<Expr>>),
+    Optional(Box<Optional<Expr>>),
+    Group(Box<Group<Expr>>),
+    Not(Box<Not<Expr>>),
+}
+
+impl Regex for Expr {
+    #[inline]
+    fn as_nfa(&self) -> NFA {
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
+
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        match self {
+            Self::Empty(r) => r.try_as_nfa(max_states),
+            Self::Char(r) => r.try_as_nfa(max_states),
+            Self::Literal(r) => r.try_as_nfa(max_states),
+            Self::Class(r) => r.try_as_nfa(max_states),
+            Self::Any(r) => r.try_as_nfa(max_states),
+            Self::Concatenation(r) => r.try_as_nfa(max_states),
+            Self::Alternative(r) => r.try_as_nfa(max_states),
+            Self::Intersection(r) => r.try_as_nfa(max_states),
+            Self::Difference(r) => r.try_as_nfa(max_states),
+            Self::Closure(r) => r.try_as_nfa(max_states),
+            Self::Some(r) => r.try_as_nfa(max_states),
+            Self::Optional(r) => r.try_as_nfa(max_states),
+            Self::Group(r) => r.try_as_nfa(max_states),
+            Self::Not(r) => r.try_as_nfa(max_states),
+        }
+    }
+}
+
+impl Debug for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty(r) => r.fmt(f),
+            Self::Char(r) => r.fmt(f),
+            Self::Literal(r) => r.chars().try_for_each(|c| write!(f, "{:?}", c)),
+            Self::Class(r) => r.fmt(f),
+            Self::Any(r) => r.fmt(f),
+            Self::Concatenation(r) => r.fmt(f),
+            Self::Alternative(r) => r.fmt(f),
+            Self::Intersection(r) => r.fmt(f),
+            Self::Difference(r) => r.fmt(f),
+            Self::Closure(r) => r.fmt(f),
+            Self::Some(r) => r.fmt(f),
+            Self::Optional(r) => r.fmt(f),
+            Self::Group(r) => r.fmt(f),
+            Self::Not(r) => r.fmt(f),
+        }
+    }
+}
+
+impl From<Empty> for Expr {
+    #[inline]
+    fn from(r: Empty) -> Self {
+        Self::Empty(r)
+    }
+}
+
+impl From<Char> for Expr {
+    #[inline]
+    fn from(r: Char) -> Self {
+        Self::Char(r)
+    }
+}
+
+impl From<String> for Expr {
+    #[inline]
+    fn from(r: String) -> Self {
+        Self::Literal(r)
+    }
+}
+
+impl From<Class> for Expr {
+    #[inline]
+    fn from(r: Class) -> Self {
+        Self::Class(r)
+    }
+}
+
+impl From<Any> for Expr {
+    #[inline]
+    fn from(r: Any) -> Self {
+        Self::Any(r)
+    }
+}
+
+impl From<Concatenation<Expr, Expr>> for Expr {
+    #[inline]
+    fn from(r: Concatenation<Expr, Expr>) -> Self {
+        Self::Concatenation(Box::new(r))
+    }
+}
+
+impl From<Alternative<Expr, Expr>> for Expr {
+    #[inline]
+    fn from(r: Alternative<Expr, Expr>) -> Self {
+        Self::Alternative(Box::new(r))
+    }
+}
+
+impl From<Intersection<Expr, Expr>> for Expr {
+    #[inline]
+    fn from(r: Intersection<Expr, Expr>) -> Self {
+        Self::Intersection(Box::new(r))
+    }
+}
+
+impl From<Difference<Expr, Expr>> for Expr {
+    #[inline]
+    fn from(r: Difference<Expr, Expr>) -> Self {
+        Self::Difference(Box::new(r))
+    }
+}
+
+impl From<Closure<Expr>> for Expr {
+    #[inline]
+    fn from(r: Closure<Expr>) -> Self {
+        Self::Closure(Box::new(r))
+    }
+}
+
+impl From<Some<Expr>> for Expr {
+    #[inline]
+    fn from(r: Some<Expr>) -> Self {
+        Self::Some(Box::new(r))
+    }
+}
+
+impl From<Optional<Expr>> for Expr {
+    #[inline]
+    fn from(r: Optional<Expr>) -> Self {
+        Self::Optional(Box::new(r))
+    }
+}
+
+impl From<Group<Expr>> for Expr {
+    #[inline]
+    fn from(r: Group<Expr>) -> Self {
+        Self::Group(Box::new(r))
+    }
+}
+
+impl From<Not<Expr>> for Expr {
+    #[inline]
+    fn from(r: Not<Expr>) -> Self {
+        Self::Not(Box::new(r))
+    }
+}
@@ -1,12 +1,30 @@
 use crate::fsa::nfa::NFA;
-use crate::regex::tokens::{Alternative, Closure, Concatenation, Some};
+use crate::fsa::CompileError;
+use crate::regex::tokens::{
+    Alternative, Any, Class, Closure, Concatenation, Difference, Group, Intersection, Not,
+    Optional, Some,
+};
 
+pub use crate::regex::expr::Expr;
+pub use crate::regex::parser::{parse, ErrorKind, ParseError};
+
+pub mod expr;
+pub mod parser;
 pub mod tokens;
 
 pub trait Regex: Sized {
     /// 转变为 NFA
     fn as_nfa(&self) -> NFA;
 
+    /// 转变为 NFA，补、交、差运算中间确定化的 DFA 状态数目超过 `max_states` 时返回错误
+    ///
+    /// 不含这些运算的正规式无须重写；组合其他正规式时应向内层传递 `max_states`
+    #[inline]
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let _ = max_states;
+        Ok(self.as_nfa())
+    }
+
     /// 连接两个正规式
     #[inline]
     fn and<R>(self, next: R) -> Concatenation<Self, R> {
@@ -19,6 +37,22 @@ pub trait Regex: Sized {
         Alternative::new(self, other)
     }
 
+    /// 同时匹配两个正规式
+    ///
+    /// 构建时须将两者确定化后求积，状态数目仅在 `try_as_nfa` 中受限
+    #[inline]
+    fn both<R>(self, other: R) -> Intersection<Self, R> {
+        Intersection::new(self, other)
+    }
+
+    /// 匹配该正规式但不匹配 `other`
+    ///
+    /// 与 `both` 相同，积构造的开销仅在 `try_as_nfa` 中受限
+    #[inline]
+    fn except<R>(self, other: R) -> Difference<Self, R> {
+        Difference::new(self, other)
+    }
+
     /// 0 次或多次匹配
     #[inline]
     fn many(self) -> Closure<Self> {
@@ -30,4 +64,54 @@ pub trait Regex: Sized {
     fn some(self) -> Some<Self> {
         Some::new(self)
     }
+
+    /// 0 次或 1 次匹配
+    #[inline]
+    fn optional(self) -> Optional<Self> {
+        Optional::new(self)
+    }
+
+    /// 捕获分组
+    #[inline]
+    fn group(self) -> Group<Self> {
+        Group::new(self)
+    }
+
+    /// 命名的捕获分组
+    #[inline]
+    fn named(self, name: impl Into<String>) -> Group<Self> {
+        Group::named(self, name)
+    }
+
+    /// 匹配所有不被该正规式匹配的字符串
+    ///
+    /// 构建时须将该正规式确定化，状态数目仅在 `try_as_nfa` 中受限
+    #[inline]
+    fn not(self) -> Not<Self> {
+        Not::new(self)
+    }
+}
+
+/// 字符范围 ([a-z])
+#[inline]
+pub fn range(start: char, end: char) -> Class {
+    Class::new([(start, end)])
+}
+
+/// 字符集合中的任一字符 ([xyz])
+#[inline]
+pub fn any_of(chars: &str) -> Class {
+    Class::new(chars.chars().map(|c| (c, c)))
+}
+
+/// 字符集合外的任一字符 ([^xyz])
+#[inline]
+pub fn none_of(chars: &str) -> Class {
+    any_of(chars).negate()
+}
+
+/// 任意字符 (.)，换行符除外
+#[inline]
+pub fn any() -> Any {
+    Any
 }
@@ -0,0 +1,658 @@
+use crate::regex::expr::Expr;
+use crate::regex::tokens::{Any, Char, Class, Empty};
+use crate::regex::{any_of, range, Regex};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+/// 将正则表达式字符串解析为正规式
+///
+/// 支持连接、选择 (`|`)、闭包 (`*`)、正闭包 (`+`)、可选 (`?`)、
+/// 分组 (捕获 `()`、命名 `(?<name>)`、非捕获 `(?:)`)、
+/// 字符类 (`[a-z]`、`[^0-9]`、`.`、`\d`、`\w`、`\s`) 及转义 (`\`)；
+/// 嵌套层数（分组、量词及选择、连接各算一层）超过 250 层时返回 `ErrorKind::TooDeep`；
+/// 字符类中的 `\d` 等不能作为范围的端点，`[\d-z]` 返回 `ErrorKind::BadRange`
+pub fn parse(pattern: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser::new(pattern);
+    let (expr, depth) = parser.alternative()?;
+    if depth > NEST_LIMIT {
+        return Err(ParseError::new(ErrorKind::TooDeep, 0..pattern.len()));
+    }
+
+    match parser.chars.next() {
+        None => Ok(expr),
+        Some((offset, _)) => Err(ParseError::new(
+            ErrorKind::UnbalancedParen,
+            offset..offset + 1,
+        )),
+    }
+}
+
+/// 解析错误
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    kind: ErrorKind,
+    span: Range<usize>,
+}
+
+/// 解析错误类型
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// 括号不匹配
+    UnbalancedParen,
+    /// 量词前缺少正规式
+    DanglingQuantifier,
+    /// 无法识别的转义序列
+    BadEscape,
+    /// 字符类未闭合
+    UnclosedClass,
+    /// 空字符类
+    EmptyClass,
+    /// 字符范围的起点大于终点，或以 `\d` 等字符类为端点
+    BadRange,
+    /// 无法识别的分组语法
+    BadGroup,
+    /// 嵌套层数超出上限（250 层）
+    TooDeep,
+}
+
+impl ParseError {
+    #[inline]
+    fn new(kind: ErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// 出错部分在正则表达式中的字节范围
+    #[inline]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// 渲染诊断信息，在正则表达式下方以 `^` 标出出错位置
+    ///
+    /// ```text
+    /// 无法识别的转义序列
+    ///   ab\q
+    ///     ^^
+    /// ```
+    pub fn render(&self, pattern: &str) -> String {
+        let column = display_width(&pattern[..self.span.start]);
+        let width = display_width(&pattern[self.span.clone()]).max(1);
+        format!(
+            "{}\n  {}\n  {}{}",
+            self,
+            pattern,
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// 字符串在终端中的显示宽度，东亚宽字符占两列
+fn display_width(str: &str) -> usize {
+    str.chars()
+        .map(|c| match c {
+            '\u{1100}'..='\u{115F}'
+            | '\u{2E80}'..='\u{A4CF}'
+            | '\u{AC00}'..='\u{D7A3}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FF60}'
+            | '\u{FFE0}'..='\u{FFE6}'
+            | '\u{20000}'..='\u{3FFFD}' => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+impl Display for ParseError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl Error for ParseError {}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            Self::UnbalancedParen => "括号不匹配",
+            Self::DanglingQuantifier => "量词前缺少正规式",
+            Self::BadEscape => "无法识别的转义序列",
+            Self::UnclosedClass => "字符类未闭合",
+            Self::EmptyClass => "空字符类",
+            Self::BadRange => "无效的字符范围",
+            Self::BadGroup => "无法识别的分组语法",
+            Self::TooDeep => "嵌套层数超出上限",
+        };
+        write!(f, "{message}")
+    }
+}
+
+/// 将多个正规式两两组合为平衡的二叉树，深度为 O(log n)，避免长列表构建及析构时递归过深
+///
+/// 选择与连接均满足结合律，组合顺序不影响语义，各分组仍按从左到右的顺序编号
+fn balanced(mut exprs: Vec<(Expr, usize)>, combine: fn(Expr, Expr) -> Expr) -> (Expr, usize) {
+    if exprs.len() == 1 {
+        return exprs.pop().unwrap();
+    }
+    let right = exprs.split_off(exprs.len() / 2);
+    let (left, left_depth) = balanced(exprs, combine);
+    let (right, right_depth) = balanced(right, combine);
+    (combine(left, right), left_depth.max(right_depth) + 1)
+}
+
+/// 将累积的字符作为一个字符串加入 `exprs`，单个字符仍为 `Char`
+fn flush(literal: &mut String, exprs: &mut Vec<(Expr, usize)>) {
+    let literal = std::mem::take(literal);
+    let mut chars = literal.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => {}
+        (Some(char), None) => exprs.push((Char::new(char).into(), 0)),
+        _ => exprs.push((literal.into(), 0)),
+    }
+}
+
+/// 正规式的最大嵌套层数
+///
+/// 正规式的构建与析构均沿嵌套结构递归，层数过深会导致栈溢出，解析时即予拒绝
+const NEST_LIMIT: usize = 250;
+
+/// 递归下降解析器，各解析函数同时返回正规式的嵌套层数，单个字符、字符类等不计层数
+///
+/// ```text
+/// alternative   := concatenation ('|' concatenation)*
+/// concatenation := repetition*
+/// repetition    := atom ('*' | '+' | '?')*
+/// atom          := '(' group alternative ')' | '[' class ']' | '.' | '\' escape | char
+/// group         := ('?:' | '?<' name '>' | '?P<' name '>')?
+/// ```
+struct Parser<'a> {
+    pattern: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    /// 当前所在的括号层数
+    parens: usize,
+}
+
+impl<'a> Parser<'a> {
+    #[inline]
+    fn new(pattern: &'a str) -> Self {
+        Self {
+            pattern,
+            chars: pattern.char_indices().peekable(),
+            parens: 0,
+        }
+    }
+
+    #[inline]
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    /// 当前位置的字节偏移
+    #[inline]
+    fn offset(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map(|&(offset, _)| offset)
+            .unwrap_or(self.pattern.len())
+    }
+
+    fn alternative(&mut self) -> Result<(Expr, usize), ParseError> {
+        let mut exprs = vec![self.concatenation()?];
+        while self.peek() == Some('|') {
+            self.chars.next();
+            exprs.push(self.concatenation()?);
+        }
+        Ok(balanced(exprs, |l, r| l.or(r).into()))
+    }
+
+    /// 相邻的字符合并为一个字符串，避免长字符串构成深层的连接
+    fn concatenation(&mut self) -> Result<(Expr, usize), ParseError> {
+        let mut exprs = Vec::new();
+        let mut literal = String::new();
+        while !matches!(self.peek(), None | Some('|' | ')')) {
+            match self.repetition()? {
+                (Expr::Char(char), _) => literal.push(char.value()),
+                next => {
+                    flush(&mut literal, &mut exprs);
+                    exprs.push(next);
+                }
+            }
+        }
+        flush(&mut literal, &mut exprs);
+
+        if exprs.is_empty() {
+            return Ok((Empty.into(), 0));
+        }
+        Ok(balanced(exprs, |l, r| l.and(r).into()))
+    }
+
+    fn repetition(&mut self) -> Result<(Expr, usize), ParseError> {
+        let (mut expr, mut depth) = self.atom()?;
+        loop {
+            let offset = self.offset();
+            expr = match self.peek() {
+                Some('*') => expr.many().into(),
+                Some('+') => expr.some().into(),
+                Some('?') => expr.optional().into(),
+                _ => return Ok((expr, depth)),
+            };
+            self.chars.next();
+
+            depth += 1;
+            if depth > NEST_LIMIT {
+                return Err(ParseError::new(ErrorKind::TooDeep, offset..offset + 1));
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<(Expr, usize), ParseError> {
+        let (offset, char) = self.chars.next().expect("调用方需保证仍有字符");
+
+        match char {
+            '(' => {
+                // 先限制括号层数，避免解析本身递归过深
+                if self.parens >= NEST_LIMIT {
+                    return Err(ParseError::new(ErrorKind::TooDeep, offset..offset + 1));
+                }
+                let group = self.group(offset)?;
+                self.parens += 1;
+                let (expr, depth) = self.alternative()?;
+                self.parens -= 1;
+
+                let (expr, depth) = match self.chars.next() {
+                    Some((_, ')')) => match group {
+                        Group::Capture(None) => (expr.group().into(), depth + 1),
+                        Group::Capture(Some(name)) => (expr.named(name).into(), depth + 1),
+                        Group::NonCapture => (expr, depth),
+                    },
+                    _ => {
+                        return Err(ParseError::new(
+                            ErrorKind::UnbalancedParen,
+                            offset..offset + 1,
+                        ))
+                    }
+                };
+                if depth > NEST_LIMIT {
+                    return Err(ParseError::new(ErrorKind::TooDeep, offset..offset + 1));
+                }
+                Ok((expr, depth))
+            }
+            '[' => Ok((self.class(offset)?.into(), 0)),
+            '.' => Ok((Any.into(), 0)),
+            '\\' => match self.escape(offset)? {
+                Escape::Char(char) => Ok((Char::new(char).into(), 0)),
+                Escape::Class(class) => Ok((class.into(), 0)),
+            },
+            '*' | '+' | '?' => Err(ParseError::new(
+                ErrorKind::DanglingQuantifier,
+                offset..offset + 1,
+            )),
+            char => Ok((Char::new(char).into(), 0)),
+        }
+    }
+
+    /// 分组类型，`offset` 为 `(` 所在位置
+    fn group(&mut self, offset: usize) -> Result<Group, ParseError> {
+        if self.peek() != Some('?') {
+            return Ok(Group::Capture(None));
+        }
+        self.chars.next();
+
+        match self.chars.next() {
+            Some((_, ':')) => return Ok(Group::NonCapture),
+            Some((_, 'P')) if self.peek() == Some('<') => {
+                self.chars.next();
+            }
+            Some((_, '<')) => {}
+            _ => return Err(ParseError::new(ErrorKind::BadGroup, offset..self.offset())),
+        }
+
+        let mut name = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '>')) if !name.is_empty() => return Ok(Group::Capture(Some(name))),
+                Some((_, c)) if c == '_' || c.is_alphanumeric() => name.push(c),
+                _ => return Err(ParseError::new(ErrorKind::BadGroup, offset..self.offset())),
+            }
+        }
+    }
+
+    /// 字符类，`offset` 为 `[` 所在位置
+    fn class(&mut self, offset: usize) -> Result<Class, ParseError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.chars.next();
+        }
+
+        let mut class = Class::new([]);
+        let mut is_empty = true;
+        loop {
+            let Some((item_offset, char)) = self.chars.next() else {
+                return Err(ParseError::new(
+                    ErrorKind::UnclosedClass,
+                    offset..offset + 1,
+                ));
+            };
+
+            let start = match char {
+                ']' => break,
+                '\\' => match self.escape(item_offset)? {
+                    Escape::Char(char) => char,
+                    // `\d` 等字符类不能作为范围的起点
+                    Escape::Class(_) if self.is_range() => {
+                        self.range_end(offset, item_offset)?;
+                        return Err(ParseError::new(
+                            ErrorKind::BadRange,
+                            item_offset..self.offset(),
+                        ));
+                    }
+                    Escape::Class(other) => {
+                        class = class.union(&other);
+                        is_empty = false;
+                        continue;
+                    }
+                },
+                char => char,
+            };
+
+            let end = if self.is_range() {
+                let end = self.range_end(offset, item_offset)?;
+                if end < start {
+                    return Err(ParseError::new(
+                        ErrorKind::BadRange,
+                        item_offset..self.offset(),
+                    ));
+                }
+                end
+            } else {
+                start
+            };
+
+            class = class.union(&Class::new([(start, end)]));
+            is_empty = false;
+        }
+
+        if is_empty {
+            return Err(ParseError::new(
+                ErrorKind::EmptyClass,
+                offset..self.offset(),
+            ));
+        }
+
+        Ok(if negated { class.negate() } else { class })
+    }
+
+    /// 字符类中接下来是否为 `-` 连接的范围，`]` 前的 `-` 为普通字符
+    #[inline]
+    fn is_range(&mut self) -> bool {
+        self.peek() == Some('-') && self.peek_second() != Some(']')
+    }
+
+    /// 跳过 `-` 并解析范围的终点，`offset` 为 `[` 所在位置，`item_offset` 为起点所在位置
+    fn range_end(&mut self, offset: usize, item_offset: usize) -> Result<char, ParseError> {
+        self.chars.next();
+        let end_offset = self.offset();
+        match self.chars.next() {
+            Some((_, '\\')) => match self.escape(end_offset)? {
+                Escape::Char(char) => Ok(char),
+                Escape::Class(_) => Err(ParseError::new(
+                    ErrorKind::BadRange,
+                    item_offset..self.offset(),
+                )),
+            },
+            Some((_, char)) => Ok(char),
+            None => Err(ParseError::new(
+                ErrorKind::UnclosedClass,
+                offset..offset + 1,
+            )),
+        }
+    }
+
+    /// 转义序列，`offset` 为 `\` 所在位置
+    fn escape(&mut self, offset: usize) -> Result<Escape, ParseError> {
+        let Some((_, char)) = self.chars.next() else {
+            return Err(ParseError::new(ErrorKind::BadEscape, offset..offset + 1));
+        };
+
+        let escape = match char {
+            'n' => Escape::Char('\n'),
+            'r' => Escape::Char('\r'),
+            't' => Escape::Char('\t'),
+            '0' => Escape::Char('\0'),
+            'd' => Escape::Class(digit()),
+            'D' => Escape::Class(digit().negate()),
+            'w' => Escape::Class(word()),
+            'W' => Escape::Class(word().negate()),
+            's' => Escape::Class(space()),
+            'S' => Escape::Class(space().negate()),
+            '\\' | '|' | '(' | ')' | '*' | '+' | '?' | '[' | ']' | '.' | '^' | '-' => {
+                Escape::Char(char)
+            }
+            _ => return Err(ParseError::new(ErrorKind::BadEscape, offset..self.offset())),
+        };
+        Ok(escape)
+    }
+
+    /// 向前看第二个字符
+    #[inline]
+    fn peek_second(&self) -> Option<char> {
+        let mut chars = self.chars.clone();
+        chars.next();
+        chars.next().map(|(_, c)| c)
+    }
+}
+
+/// 分组类型
+enum Group {
+    /// 捕获分组，可带名称
+    Capture(Option<String>),
+    /// 非捕获分组 (?:)
+    NonCapture,
+}
+
+/// 转义结果
+enum Escape {
+    Char(char),
+    Class(Class),
+}
+
+/// 数字 (\d)
+#[inline]
+fn digit() -> Class {
+    range('0', '9')
+}
+
+/// 单词字符 (\w)
+#[inline]
+fn word() -> Class {
+    Class::new([('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')])
+}
+
+/// 空白字符 (\s)
+#[inline]
+fn space() -> Class {
+    any_of(" \t\n\r\u{B}\u{C}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, ErrorKind, NEST_LIMIT};
+    use crate::regex::{any, any_of, Regex};
+    use crate::Matcher;
+    use std::ops::Range;
+
+    /// 检查错误类型、字节范围及渲染出的 `^` 行
+    fn check(pattern: &str, kind: ErrorKind, span: Range<usize>, caret: &str) {
+        let error = parse(pattern).unwrap_err();
+        assert_eq!(error.kind(), kind, "{pattern}");
+        assert_eq!(error.span(), span, "{pattern}");
+
+        let rendered = error.render(pattern);
+        let lines: Vec<_> = rendered.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                kind.to_string(),
+                format!("  {pattern}"),
+                format!("  {caret}")
+            ]
+        );
+    }
+
+    #[test]
+    fn unbalanced_paren() {
+        check("ab)", ErrorKind::UnbalancedParen, 2..3, "  ^");
+        check("中(文", ErrorKind::UnbalancedParen, 3..4, "  ^");
+    }
+
+    #[test]
+    fn dangling_quantifier() {
+        check("*a", ErrorKind::DanglingQuantifier, 0..1, "^");
+        check("文|+", ErrorKind::DanglingQuantifier, 4..5, "   ^");
+    }
+
+    #[test]
+    fn bad_escape() {
+        check("字\\q", ErrorKind::BadEscape, 3..5, "  ^^");
+        check("a\\", ErrorKind::BadEscape, 1..2, " ^");
+    }
+
+    #[test]
+    fn unclosed_class() {
+        check("中[ab", ErrorKind::UnclosedClass, 3..4, "  ^");
+    }
+
+    #[test]
+    fn empty_class() {
+        check("[]", ErrorKind::EmptyClass, 0..2, "^^");
+        check("中[^]", ErrorKind::EmptyClass, 3..6, "  ^^^");
+    }
+
+    #[test]
+    fn bad_range() {
+        check("文[z-a]", ErrorKind::BadRange, 4..7, "   ^^^");
+        check("[中-一]", ErrorKind::BadRange, 1..8, " ^^^^^");
+    }
+
+    #[test]
+    fn bad_group() {
+        check("中(?x)", ErrorKind::BadGroup, 3..6, "  ^^^");
+        check("中(?<名", ErrorKind::BadGroup, 3..9, "  ^^^^^");
+    }
+
+    #[test]
+    fn too_deep() {
+        let pattern = format!("中{}", nested("(", NEST_LIMIT + 1));
+        let caret = format!("{}^", " ".repeat(2 + NEST_LIMIT));
+        let offset = 3 + NEST_LIMIT;
+        check(&pattern, ErrorKind::TooDeep, offset..offset + 1, &caret);
+    }
+
+    /// `n` 层嵌套的分组，`open` 为左括号
+    fn nested(open: &str, n: usize) -> String {
+        format!("{}a{}", open.repeat(n), ")".repeat(n))
+    }
+
+    #[test]
+    fn nest_limit_boundary() {
+        for open in ["(", "(?:", "(?<g>"] {
+            let pattern = nested(open, NEST_LIMIT);
+            let matcher = Matcher::from_regex(parse(&pattern).unwrap());
+            assert!(matcher.is_matched("a"), "{open}");
+
+            let pattern = nested(open, NEST_LIMIT + 1);
+            assert_eq!(
+                parse(&pattern).unwrap_err().kind(),
+                ErrorKind::TooDeep,
+                "{open}"
+            );
+        }
+
+        let pattern = format!("a{}", "?".repeat(NEST_LIMIT));
+        assert!(parse(&pattern).is_ok());
+        let pattern = format!("a{}", "?".repeat(NEST_LIMIT + 1));
+        let error = parse(&pattern).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TooDeep);
+        assert_eq!(error.span(), pattern.len() - 1..pattern.len());
+    }
+
+    #[test]
+    fn class_escape_is_not_range_endpoint() {
+        for (pattern, span) in [("[\\d-z]", 1..5), ("[a\\w-\\d]", 2..7), ("[z-\\d]", 1..5)] {
+            let error = parse(pattern).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::BadRange, "{pattern}");
+            assert_eq!(error.span(), span, "{pattern}");
+        }
+        assert_eq!(parse("[\\d-").unwrap_err().kind(), ErrorKind::UnclosedClass);
+
+        // `]` 前的 `-` 仍为普通字符
+        let matcher = Matcher::from_regex(parse("[\\d-]+").unwrap());
+        assert!(matcher.is_matched("1-2"));
+        assert!(!matcher.is_matched("z"));
+    }
+
+    /// 解析结果与 `expected` 所匹配的语言相同；不同时返回最短的区分串
+    fn language(pattern: &str, expected: impl Regex) -> Result<(), String> {
+        let dfa = parse(pattern).unwrap().as_nfa().as_dfa();
+        dfa.equivalent(&expected.as_nfa().as_dfa())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(language("ab|c", "ab".or("c")), Ok(()));
+        assert_eq!(language("ab|c", "a".and("b".or("c"))), Err("c".to_string()));
+        assert_eq!(language("ab*", "a".and("b".many())), Ok(()));
+        assert_eq!(language("ab*", "ab".many()), Err(String::new()));
+        assert_eq!(
+            language("a|bc*|d+", "a".or("b".and("c".many())).or("d".some())),
+            Ok(())
+        );
+        assert_eq!(language("a|", "a".or("")), Ok(()));
+        assert_eq!(language("(a|b)c?", "a".or("b").and("c".optional())), Ok(()));
+        // 量词可以连用，各自作用于左侧的整体
+        assert_eq!(language("a+?", "a".some().optional()), Ok(()));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(language("\\*\\+\\?\\(\\)\\|\\\\\\.", "*+?()|\\."), Ok(()));
+        assert_eq!(language("\\[\\]\\^\\-", "[]^-"), Ok(()));
+        assert_eq!(language("\\n\\r\\t\\0", "\n\r\t\0"), Ok(()));
+        assert_eq!(language("a\\.b", "a.b"), Ok(()));
+        assert_eq!(language("\\d", any_of("0123456789")), Ok(()));
+        assert_eq!(language("[\\d\\-x]", any_of("0123456789-x")), Ok(()));
+        assert_eq!(language("[^\\n]", any()), Ok(()));
+    }
+
+    #[test]
+    fn nested_groups() {
+        assert_eq!(language("((a)(b(c)))", "abc"), Ok(()));
+        assert_eq!(
+            language("(?:a(?<x>b|c))*d", "a".and("b".or("c")).many().and("d")),
+            Ok(())
+        );
+
+        // 分组按左括号出现的顺序编号，非捕获分组不占编号
+        let matcher = Matcher::from_regex(parse("((a)(?:b(?P<c>c)))(d)").unwrap());
+        let captures = matcher.captures("abcd").unwrap();
+        let groups = captures
+            .iter()
+            .map(|m| m.map(|m| m.range()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [Some(0..4), Some(0..3), Some(0..1), Some(2..3), Some(3..4)]
+        );
+        assert_eq!(captures.name("c").map(|m| m.range()), Some(2..3));
+    }
+}
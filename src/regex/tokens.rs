@@ -1,5 +1,7 @@
-use crate::fsa::nfa::{self, NFA};
+use crate::fsa::nfa::NFA;
+use crate::fsa::{next_char, prev_char, CompileError, Symbol};
 use crate::regex::Regex;
+use std::cmp::Ordering;
 use std::fmt::{Debug, Formatter};
 
 /// 字符 (a)
@@ -11,15 +13,21 @@ impl Char {
     pub fn new(char: char) -> Self {
         Self(char)
     }
+
+    #[inline]
+    pub fn value(&self) -> char {
+        self.0
+    }
 }
 
 impl Regex for Char {
     fn as_nfa(&self) -> NFA {
-        let (start, end) = (nfa::State::new_node(), nfa::State::new_node());
+        let mut nfa = NFA::new();
+        let (start, end) = (nfa.start(), nfa.end());
 
-        start.borrow_mut().transition(self.0.into(), end.clone());
+        nfa.transition(start, self.0.into(), end);
 
-        NFA::new(start, end)
+        nfa
     }
 }
 
@@ -46,12 +54,19 @@ where
     L: Regex,
     R: Regex,
 {
+    #[inline]
     fn as_nfa(&self) -> NFA {
-        let (left, right) = (self.0.as_nfa(), self.1.as_nfa());
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
+
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let mut nfa = self.0.try_as_nfa(max_states)?;
+        let (start, end) = nfa.embed(self.1.try_as_nfa(max_states)?);
 
-        left.end().borrow_mut().e_transition(right.start());
+        nfa.e_transition(nfa.end(), start);
+        nfa.set_end(end);
 
-        NFA::new(left.start(), right.end())
+        Ok(nfa)
     }
 }
 
@@ -82,19 +97,26 @@ where
     L: Regex,
     R: Regex,
 {
+    #[inline]
     fn as_nfa(&self) -> NFA {
-        let (start, end) = (nfa::State::new_node(), nfa::State::new_node());
-        let (left, right) = (self.0.as_nfa(), self.1.as_nfa());
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
 
-        start
-            .borrow_mut()
-            .e_transition(left.start())
-            .e_transition(right.start());
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        // 在左侧的 NFA 上直接构建，只并入右侧，避免逐层复制整个左侧
+        let mut nfa = self.0.try_as_nfa(max_states)?;
+        let left = (nfa.start(), nfa.end());
+        let right = nfa.embed(self.1.try_as_nfa(max_states)?);
+        let (start, end) = (nfa.add_state(), nfa.add_state());
 
-        left.end().borrow_mut().e_transition(end.clone());
-        right.end().borrow_mut().e_transition(end.clone());
+        nfa.e_transition(start, left.0).e_transition(start, right.0);
 
-        NFA::new(start, end)
+        nfa.e_transition(left.1, end);
+        nfa.e_transition(right.1, end);
+
+        nfa.set_start(start);
+        nfa.set_end(end);
+        Ok(nfa)
     }
 }
 
@@ -109,6 +131,102 @@ where
     }
 }
 
+/// 交运算 (a&b)：匹配同时被两者匹配的字符串
+///
+/// 经由 DFA 的积构造，原正规式中的捕获分组不再保留；
+/// `as_nfa` 的确定化与积构造不受限制，以 `try_as_nfa` 构建时受 `max_states` 限制
+#[derive(Clone)]
+pub struct Intersection<L, R>(L, R);
+
+impl<L, R> Intersection<L, R> {
+    #[inline]
+    pub fn new(l: L, r: R) -> Self {
+        Self(l, r)
+    }
+}
+
+impl<L, R> Regex for Intersection<L, R>
+where
+    L: Regex,
+    R: Regex,
+{
+    #[inline]
+    fn as_nfa(&self) -> NFA {
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
+
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let left = self
+            .0
+            .try_as_nfa(max_states)?
+            .as_dfa_with_limit(max_states)?;
+        let right = self
+            .1
+            .try_as_nfa(max_states)?
+            .as_dfa_with_limit(max_states)?;
+        Ok(left.intersect_with_limit(&right, max_states)?.as_nfa())
+    }
+}
+
+impl<L, R> Debug for Intersection<L, R>
+where
+    L: Debug,
+    R: Debug,
+{
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({:?}&{:?})", self.0, self.1)
+    }
+}
+
+/// 差运算 (a-b)：匹配被前者匹配而不被后者匹配的字符串
+///
+/// 经由 DFA 的积构造，原正规式中的捕获分组不再保留；
+/// `as_nfa` 的确定化与积构造不受限制，以 `try_as_nfa` 构建时受 `max_states` 限制
+#[derive(Clone)]
+pub struct Difference<L, R>(L, R);
+
+impl<L, R> Difference<L, R> {
+    #[inline]
+    pub fn new(l: L, r: R) -> Self {
+        Self(l, r)
+    }
+}
+
+impl<L, R> Regex for Difference<L, R>
+where
+    L: Regex,
+    R: Regex,
+{
+    #[inline]
+    fn as_nfa(&self) -> NFA {
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
+
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let left = self
+            .0
+            .try_as_nfa(max_states)?
+            .as_dfa_with_limit(max_states)?;
+        let right = self
+            .1
+            .try_as_nfa(max_states)?
+            .as_dfa_with_limit(max_states)?;
+        Ok(left.difference_with_limit(&right, max_states)?.as_nfa())
+    }
+}
+
+impl<L, R> Debug for Difference<L, R>
+where
+    L: Debug,
+    R: Debug,
+{
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({:?}-{:?})", self.0, self.1)
+    }
+}
+
 /// 闭包运算 (a*)
 #[derive(Clone)]
 pub struct Closure<R>(R);
@@ -124,21 +242,22 @@ impl<R> Regex for Closure<R>
 where
     R: Regex,
 {
+    #[inline]
     fn as_nfa(&self) -> NFA {
-        let (start, end) = (nfa::State::new_node(), nfa::State::new_node());
-        let inner = self.0.as_nfa();
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
 
-        start
-            .borrow_mut()
-            .e_transition(end.clone())
-            .e_transition(inner.start());
-        inner
-            .end()
-            .borrow_mut()
-            .e_transition(inner.start())
-            .e_transition(end.clone());
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let mut nfa = NFA::new();
+        let (start, end) = (nfa.start(), nfa.end());
+        let inner = nfa.embed(self.0.try_as_nfa(max_states)?);
 
-        NFA::new(start, end)
+        // 先添加进入闭包的转移，捕获分组时尽可能多地重复
+        nfa.e_transition(start, inner.0).e_transition(start, end);
+        nfa.e_transition(inner.1, inner.0)
+            .e_transition(inner.1, end);
+
+        Ok(nfa)
     }
 }
 
@@ -165,11 +284,24 @@ impl<R> Some<R> {
 
 impl<R> Regex for Some<R>
 where
-    R: Regex + Clone,
+    R: Regex,
 {
     #[inline]
     fn as_nfa(&self) -> NFA {
-        Concatenation::new(self.0.clone(), Closure::new(self.0.clone())).as_nfa()
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
+
+    /// 内部正规式只构造一次，以免其中的捕获分组重复编号
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let mut nfa = NFA::new();
+        let (start, end) = (nfa.start(), nfa.end());
+        let inner = nfa.embed(self.0.try_as_nfa(max_states)?);
+
+        nfa.e_transition(start, inner.0);
+        nfa.e_transition(inner.1, inner.0)
+            .e_transition(inner.1, end);
+
+        Ok(nfa)
     }
 }
 
@@ -183,7 +315,307 @@ where
     }
 }
 
-/// 字符串
+/// 可选 (a?)
+#[derive(Clone)]
+pub struct Optional<R>(R);
+
+impl<R> Optional<R> {
+    #[inline]
+    pub fn new(r: R) -> Self {
+        Self(r)
+    }
+}
+
+impl<R> Regex for Optional<R>
+where
+    R: Regex,
+{
+    #[inline]
+    fn as_nfa(&self) -> NFA {
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
+
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let mut nfa = NFA::new();
+        let (start, end) = (nfa.start(), nfa.end());
+        let inner = nfa.embed(self.0.try_as_nfa(max_states)?);
+
+        nfa.e_transition(start, inner.0).e_transition(start, end);
+        nfa.e_transition(inner.1, end);
+
+        Ok(nfa)
+    }
+}
+
+impl<R> Debug for Optional<R>
+where
+    R: Debug,
+{
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({:?})?", self.0)
+    }
+}
+
+/// 捕获分组 ((a)、(?<name>a))，编号按左括号出现的顺序自动分配
+#[derive(Clone)]
+pub struct Group<R> {
+    regex: R,
+    name: Option<String>,
+}
+
+impl<R> Group<R> {
+    #[inline]
+    pub fn new(r: R) -> Self {
+        Self {
+            regex: r,
+            name: None,
+        }
+    }
+
+    #[inline]
+    pub fn named(r: R, name: impl Into<String>) -> Self {
+        Self {
+            regex: r,
+            name: Option::Some(name.into()),
+        }
+    }
+
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+impl<R> Regex for Group<R>
+where
+    R: Regex,
+{
+    #[inline]
+    fn as_nfa(&self) -> NFA {
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
+
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let mut nfa = NFA::new();
+        let (start, end) = (nfa.start(), nfa.end());
+        let group = nfa.add_group(self.name.clone());
+        let inner = nfa.embed(self.regex.try_as_nfa(max_states)?);
+
+        nfa.transition(start, Symbol::Tag(group * 2), inner.0);
+        nfa.transition(inner.1, Symbol::Tag(group * 2 + 1), end);
+
+        Ok(nfa)
+    }
+}
+
+impl<R> Debug for Group<R>
+where
+    R: Debug,
+{
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Option::Some(name) => write!(f, "(?<{}>{:?})", name, self.regex),
+            None => write!(f, "({:?})", self.regex),
+        }
+    }
+}
+
+/// 补 (!a)：匹配所有不被原正规式匹配的字符串
+///
+/// 经由 DFA 的补运算构造，原正规式中的捕获分组不再保留；
+/// `as_nfa` 的确定化不受限制，以 `try_as_nfa` 构建（如 `Matcher::from_regex_with`）时受 `max_states` 限制
+#[derive(Clone)]
+pub struct Not<R>(R);
+
+impl<R> Not<R> {
+    #[inline]
+    pub fn new(r: R) -> Self {
+        Self(r)
+    }
+}
+
+impl<R> Regex for Not<R>
+where
+    R: Regex,
+{
+    #[inline]
+    fn as_nfa(&self) -> NFA {
+        self.try_as_nfa(usize::MAX).unwrap()
+    }
+
+    fn try_as_nfa(&self, max_states: usize) -> Result<NFA, CompileError> {
+        let dfa = self
+            .0
+            .try_as_nfa(max_states)?
+            .as_dfa_with_limit(max_states)?;
+        Ok(dfa.complement().as_nfa())
+    }
+}
+
+impl<R> Debug for Not<R>
+where
+    R: Debug,
+{
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "!({:?})", self.0)
+    }
+}
+
+/// 空串 (ε)
+#[derive(Clone, Copy, Default)]
+pub struct Empty;
+
+impl Regex for Empty {
+    fn as_nfa(&self) -> NFA {
+        let mut nfa = NFA::new();
+        let (start, end) = (nfa.start(), nfa.end());
+
+        nfa.e_transition(start, end);
+
+        nfa
+    }
+}
+
+impl Debug for Empty {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ε")
+    }
+}
+
+/// 字符类 ([a-z]、[^0-9])，由有序、互不相交且不相邻的字符区间构成
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Class(Vec<(char, char)>);
+
+impl Class {
+    /// 由任意字符区间构建，区间可重叠、乱序
+    pub fn new(ranges: impl IntoIterator<Item = (char, char)>) -> Self {
+        let mut ranges = ranges
+            .into_iter()
+            .filter(|(start, end)| start <= end)
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Option::Some((_, last)) if next_char(*last).is_none_or(|c| c >= start) => {
+                    *last = (*last).max(end)
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+
+        Self(merged)
+    }
+
+    /// 取反 ([^...])
+    pub fn negate(&self) -> Self {
+        let mut ranges = Vec::with_capacity(self.0.len() + 1);
+        let mut start = Option::Some('\0');
+
+        for &(lo, hi) in &self.0 {
+            if let Option::Some(start) = start.filter(|&c| c < lo) {
+                ranges.push((start, prev_char(lo).unwrap()));
+            }
+            start = next_char(hi);
+        }
+        if let Option::Some(start) = start {
+            ranges.push((start, char::MAX));
+        }
+
+        Self(ranges)
+    }
+
+    /// 并集
+    #[inline]
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.0.iter().chain(&other.0).copied())
+    }
+
+    #[inline]
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.0
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    pub fn contains(&self, char: char) -> bool {
+        self.0
+            .binary_search_by(|&(start, end)| {
+                if end < char {
+                    Ordering::Less
+                } else if start > char {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+}
+
+impl Regex for Class {
+    fn as_nfa(&self) -> NFA {
+        let mut nfa = NFA::new();
+        let (start, end) = (nfa.start(), nfa.end());
+
+        for &(lo, hi) in &self.0 {
+            nfa.transition(start, Symbol::Range(lo, hi), end);
+        }
+
+        nfa
+    }
+}
+
+impl Debug for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for &(start, end) in &self.0 {
+            if start == end {
+                write!(f, "{}", start.escape_debug())?;
+            } else {
+                write!(f, "{}-{}", start.escape_debug(), end.escape_debug())?;
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+/// 任意字符 (.)，换行符除外
+#[derive(Clone, Copy, Default)]
+pub struct Any;
+
+impl Any {
+    /// 等价的字符类
+    #[inline]
+    pub fn class(&self) -> Class {
+        Class::new([('\n', '\n')]).negate()
+    }
+}
+
+impl Regex for Any {
+    #[inline]
+    fn as_nfa(&self) -> NFA {
+        self.class().as_nfa()
+    }
+}
+
+impl Debug for Any {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, ".")
+    }
+}
+
+/// 字符串，空串即为 ε
 impl<T> Regex for T
 where
     T: AsRef<str>,
@@ -193,10 +625,97 @@ where
             .chars()
             .map(Char)
             .map(|c| c.as_nfa())
-            .reduce(|l, r| {
-                l.end().borrow_mut().e_transition(r.start());
-                NFA::new(l.start(), r.end())
+            .reduce(|mut l, r| {
+                let (start, end) = l.embed(r);
+                l.e_transition(l.end(), start);
+                l.set_end(end);
+                l
             })
-            .expect("正规式不能为空！")
+            .unwrap_or_else(|| Empty.as_nfa())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::regex::tokens::Class;
+    use crate::regex::{any_of, none_of, range};
+    use crate::Matcher;
+
+    #[test]
+    fn range_any_of_none_of() {
+        let lower = range('a', 'z');
+        assert_eq!(lower.ranges(), [('a', 'z')]);
+        assert!(lower.contains('a') && lower.contains('m') && lower.contains('z'));
+        assert!(!lower.contains('`') && !lower.contains('{'));
+        assert!(range('z', 'a').is_empty());
+
+        assert_eq!(any_of("xyz").ranges(), [('x', 'z')]);
+        assert_eq!(any_of("zxzy").ranges(), [('x', 'z')]);
+        assert_eq!(any_of("xz").ranges(), [('x', 'x'), ('z', 'z')]);
+        assert!(any_of("").is_empty());
+        assert_eq!(none_of("xyz").ranges(), [('\0', 'w'), ('{', char::MAX)]);
+
+        let matcher = Matcher::from_regex(none_of("xyz"));
+        for (input, matched) in [
+            ("a", true),
+            ("中", true),
+            ("\0", true),
+            ("y", false),
+            ("ab", false),
+        ] {
+            assert_eq!(matcher.is_matched(input), matched, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn merge_adjacent_and_overlapping_ranges() {
+        let class = Class::new([
+            ('d', 'f'),
+            ('a', 'a'),
+            ('b', 'c'),
+            ('y', 'y'),
+            ('x', 'z'),
+            ('q', 'p'),
+        ]);
+        assert_eq!(class.ranges(), [('a', 'f'), ('x', 'z')]);
+
+        // 两端的字符及代理区两侧的字符同样视作相邻
+        let class = Class::new([('\u{1}', '\u{1}'), ('\0', '\0')]);
+        assert_eq!(class.ranges(), [('\0', '\u{1}')]);
+        let class = Class::new([(char::MAX, char::MAX), ('\u{10FFFE}', '\u{10FFFE}')]);
+        assert_eq!(class.ranges(), [('\u{10FFFE}', char::MAX)]);
+        let class = Class::new([('\u{E000}', '\u{E000}'), ('\u{D7FF}', '\u{D7FF}')]);
+        assert_eq!(class.ranges(), [('\u{D7FF}', '\u{E000}')]);
+        let class = Class::new([('\0', char::MAX), ('a', 'z')]);
+        assert_eq!(class.ranges(), [('\0', char::MAX)]);
+    }
+
+    #[test]
+    fn negate_at_edges() {
+        let negated = |ranges: &[(char, char)]| Class::new(ranges.iter().copied()).negate();
+        assert_eq!(negated(&[]).ranges(), [('\0', char::MAX)]);
+        assert!(negated(&[('\0', char::MAX)]).is_empty());
+        assert_eq!(negated(&[('\0', '\0')]).ranges(), [('\u{1}', char::MAX)]);
+        assert_eq!(
+            negated(&[(char::MAX, char::MAX)]).ranges(),
+            [('\0', '\u{10FFFE}')]
+        );
+        assert_eq!(
+            negated(&[('\0', 'a'), (char::MAX, char::MAX)]).ranges(),
+            [('b', '\u{10FFFE}')]
+        );
+        assert_eq!(
+            negated(&[('\u{D7FF}', '\u{E000}')]).ranges(),
+            [('\0', '\u{D7FE}'), ('\u{E001}', char::MAX)]
+        );
+
+        for class in [
+            range('a', 'z'),
+            any_of("\0xyz"),
+            none_of("xyz"),
+            Class::new([]),
+        ] {
+            assert_eq!(class.negate().negate().ranges(), class.ranges());
+        }
     }
 }